@@ -0,0 +1,147 @@
+//! Battery readings as discovered on sysfs
+
+use crate::configuration::CapacityBasis;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Battery status enum. 'Passive' denotes the 'Unknown' state provided by sysfs
+/// when TLP enforces a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Charging,
+    Discharging,
+    Passive,
+    /// Passive between the charge start and stop thresholds, charging only once below the start
+    Waiting,
+    /// Some batteries charging while others discharge, e.g. during a hand-over between them. Only
+    /// ever the status of several batteries together
+    Mixed,
+}
+
+impl Status {
+    /// Status of several batteries together: mixed if some charge while others discharge, else
+    /// charging or discharging if any is, else waiting if any is
+    pub fn aggregate(statuses: impl IntoIterator<Item = Status>) -> Status {
+        let mut aggregate = Status::Passive;
+        for status in statuses {
+            aggregate = match (aggregate, status) {
+                (Status::Mixed, _) | (_, Status::Mixed) => Status::Mixed,
+                (Status::Charging, Status::Discharging) | (Status::Discharging, Status::Charging) => Status::Mixed,
+                (Status::Charging, _) | (_, Status::Charging) => Status::Charging,
+                (Status::Discharging, _) | (_, Status::Discharging) => Status::Discharging,
+                (Status::Waiting, _) | (_, Status::Waiting) => Status::Waiting,
+                (Status::Passive, Status::Passive) => Status::Passive,
+            };
+        }
+        aggregate
+    }
+
+    /// Signed energy flow in µW into a battery of this status at given power: positive when
+    /// charging, negative when discharging, and none otherwise
+    pub fn flow(&self, power: u32) -> i64 {
+        match self {
+            Status::Charging => power as i64,
+            Status::Discharging => -(power as i64),
+            Status::Passive | Status::Waiting | Status::Mixed => 0,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Charging => "charging",
+            Status::Discharging => "discharging",
+            Status::Passive => "passive",
+            Status::Waiting => "waiting",
+            Status::Mixed => "mixed",
+        })
+    }
+}
+
+impl FromStr for Status {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Status, String> {
+        match s {
+            "charging" => Ok(Status::Charging),
+            "discharging" => Ok(Status::Discharging),
+            "passive" => Ok(Status::Passive),
+            "waiting" => Ok(Status::Waiting),
+            "mixed" => Ok(Status::Mixed),
+            _ => Err(format!("Unknown status: {}", s)),
+        }
+    }
+}
+
+/// A battery and all its concomitant data. Note that units are normalized to energy as provided
+/// by sysfs in micros, converting from charge where a battery only exposes 'charge_*' attributes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Battery {
+    /// Name of the battery on sysfs, e.g. 'BAT0'
+    pub name: String,
+    pub status: Status,
+    /// Unit: µWh
+    pub current_charge: u32,
+    /// Unit: µWh
+    pub max_charge: u32,
+    /// Capacity as designed, if reported, unit: µWh
+    pub design_charge: Option<u32>,
+    /// Unit: µW
+    pub power_draw: u32,
+    /// Charge stop threshold as a fraction of max charge
+    pub tlp_threshold: f32,
+    /// Charge start threshold as a fraction of max charge, if reported
+    pub start_threshold: Option<f32>,
+}
+
+impl Battery {
+    /// Charge of this battery as a fraction of its max charge, being zero without any capacity
+    pub fn percentage(&self) -> f32 {
+        if self.max_charge == 0 {
+            return 0f32;
+        }
+        self.current_charge as f32 / self.max_charge as f32
+    }
+
+    /// Signed energy flow into this battery in µW, positive when charging
+    pub fn net_flow(&self) -> i64 {
+        self.status.flow(self.power_draw)
+    }
+
+    /// Capacity of this battery on given basis in µWh, using max charge where no design capacity
+    /// is reported
+    pub fn capacity(&self, basis: CapacityBasis) -> u32 {
+        match basis {
+            CapacityBasis::Full => self.max_charge,
+            CapacityBasis::Design => self.design_charge.unwrap_or(self.max_charge),
+            CapacityBasis::Threshold => self.charge_target(CapacityBasis::Full),
+        }
+    }
+
+    /// Charge in µWh at which this battery stops charging, being its threshold of the capacity on
+    /// given basis. As a battery cannot hold more than its max charge, the target is capped by it
+    pub fn charge_target(&self, basis: CapacityBasis) -> u32 {
+        let capacity = match basis {
+            CapacityBasis::Design => self.capacity(basis),
+            CapacityBasis::Full | CapacityBasis::Threshold => self.max_charge,
+        };
+        ((capacity as f32 * self.tlp_threshold) as u32).min(self.max_charge)
+    }
+
+    /// Charge of this battery as a fraction of its capacity on given basis, being zero without any
+    /// capacity
+    pub fn percentage_of(&self, basis: CapacityBasis) -> f32 {
+        let capacity = self.capacity(basis);
+        if capacity == 0 {
+            return 0f32;
+        }
+        let percentage = self.current_charge as f32 / capacity as f32;
+        match basis {
+            CapacityBasis::Threshold => percentage.min(1f32),
+            _ => percentage,
+        }
+    }
+}
@@ -0,0 +1,403 @@
+//! Command-line options of the status-bar binary
+
+use poly_battery_status::{
+    CapacityBasis, Discovery, Estimator, Format, LogFormat, PerBattery, Rotation, State, Style, PSEUDO_FS_PATH, SYSFS_ROOT_ENV,
+};
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: poly-battery-status [OPTIONS] [THRESHOLD COMMAND [ARGS...]]
+       poly-battery-status log [OPTIONS] [FILE]
+       poly-battery-status report [OPTIONS] [FILE]
+       poly-battery-status graph [OPTIONS] [FILE]
+       poly-battery-status health [OPTIONS]
+
+Prints the aggregated status of all batteries. If THRESHOLD and COMMAND are given,
+COMMAND is executed when the charge percentage is at or below THRESHOLD.
+
+Subcommands:
+  log                           Append a reading of all batteries to FILE, or every interval with
+                                --watch [default: $XDG_STATE_HOME/poly-battery-status/history.jsonl]
+  report                        Print per-day discharge statistics of the history log FILE,
+                                including its rotated logs
+  graph                         Chart recent charge and power draw of the history log FILE
+  health                        Print wear, cycle count and identification of each battery
+
+Options:
+  --sysfs-root <PATH>           Read power supplies from PATH instead of /sys/class/power_supply/
+                                (also POLY_BATTERY_STATUS_SYSFS_ROOT)
+  --no-battery <empty|ac|TEXT>  Output when no batteries are found [default: empty]
+  --no-battery-exit-code <CODE> Exit code when no batteries are found [default: 6]
+  --format <FORMAT>             Output format: plain, i3blocks-json, i3bar, waybar, polybar,
+                                lemonbar, or a template such as '{percent:.0}%[ ({sign}{time})]'
+                                [default: plain]
+  --warning <PERCENT>           Percentage at or below which charge is low [default: 30]
+  --critical <PERCENT>          Percentage at or below which charge is critical and urgent [default: 15]
+  --color-normal <COLOR>        Color when not low, or 'none' [default: none]
+  --color-charging <COLOR>      Color when charging, or 'none' [default: #00FF00]
+  --color-warning <COLOR>       Color when low, or 'none' [default: #FFFF00]
+  --color-critical <COLOR>      Color when critical, or 'none' [default: #FF0000]
+  --ramp-charging <GLYPHS>      Comma-separated icons from empty to full when charging
+  --ramp-discharging <GLYPHS>   Comma-separated icons from empty to full when discharging
+  --ramp-passive <GLYPHS>       Comma-separated icons from empty to full when passive
+  --per-battery                 List every battery separately, e.g. 'BAT0 78% | BAT1 12% (-1:40)'
+  --battery-separator <TEXT>    Text between batteries when per-battery [default: ' | ']
+  --battery-order <ORDER>       Order of batteries when per-battery: name, percentage (lowest
+                                first), or comma-separated names such as 'BAT1,BAT0' [default: name]
+  --watch <INTERVAL>            Keep running, printing a line whenever the output changes, refreshing
+                                every INTERVAL (e.g. '5', '5s', '500ms' or '1m') and on SIGUSR1
+  --no-uevents                  Only poll when watching, instead of also refreshing on power supply
+                                uevents such as AC plug and unplug
+  --include <PATTERNS>          Only consider batteries whose name matches any of these
+                                comma-separated patterns, where '*' matches any text and '?' any
+                                single character, e.g. 'BAT*,CMB?' (repeatable)
+  --exclude <PATTERNS>          Ignore batteries whose name matches any of these patterns (repeatable)
+  --peripherals                 List batteries of peripherals, such as mice and headsets, with
+                                their charge and status instead of the status line
+  --peripheral-icons            Append a compact list of peripherals to the status line, marking
+                                those at or below --warning with '!'
+  --default-threshold <PERCENT> Charge stop threshold of batteries not reporting one [default: 80]
+  --capacity-basis <BASIS>      Capacity percentage and time-to-full are relative to: full (when
+                                last fully charged), design, or threshold (charge at the stop
+                                threshold) [default: full]
+  --estimator <ESTIMATOR>       Power draw used for time-to-completion: instant, ema (moving
+                                average) or regression (slope of energy) [default: instant]
+  --estimator-window <INTERVAL> Time constant or window of the ema and regression estimators
+                                [default: 5m]
+  --state                       Keep recent readings in
+                                $XDG_STATE_HOME/poly-battery-status/state.json, letting
+                                estimators smooth across invocations
+  --state-file <PATH>           Keep recent readings in PATH instead
+  --state-max-age <INTERVAL>    Age after which readings are pruned from state [default: 1h]
+  --log-format <csv|jsonl>      Format of the history log [default: by extension, else jsonl]
+  --log-max-size <SIZE>         Rotate the log beyond SIZE bytes, e.g. '512K' or '10M', or 'none'
+                                [default: 10M]
+  --log-max-age <INTERVAL>      Rotate the log once its first reading is older than INTERVAL
+  --log-keep <N>                Number of rotated logs kept [default: 3]
+  --json                        Print the report, health or peripherals as JSON instead of a table
+  --hours <N>                   Hours of history charted by graph [default: 24]
+  --width <N>                   Width of the graph [default: terminal width]
+  --height <N>                  Rows of the graph's charge chart [default: 8]
+  -h, --help                    Print this help
+";
+
+/// Output printed when a machine has no batteries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoBattery {
+    /// Print an empty line, letting status bars hide the block
+    Empty,
+    /// Print 'AC'
+    Ac,
+    /// Print a custom string
+    Text(String),
+}
+
+impl NoBattery {
+    fn parse(value: &str) -> NoBattery {
+        match value {
+            "empty" => NoBattery::Empty,
+            "ac" => NoBattery::Ac,
+            text => NoBattery::Text(text.to_string()),
+        }
+    }
+
+    /// Line to print for this mode
+    pub fn text(&self) -> &str {
+        match self {
+            NoBattery::Empty => "",
+            NoBattery::Ac => "AC",
+            NoBattery::Text(text) => text,
+        }
+    }
+}
+
+/// Mode of operation, selected by an optional first positional argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subcommand {
+    /// Print the status line
+    Status,
+    /// Append readings to a history log
+    Log,
+    /// Summarize a history log
+    Report,
+    /// Chart a history log
+    Graph,
+    /// Report wear of batteries
+    Health,
+}
+
+impl Subcommand {
+    fn parse(name: &str) -> Option<Subcommand> {
+        match name {
+            "log" => Some(Subcommand::Log),
+            "report" => Some(Subcommand::Report),
+            "graph" => Some(Subcommand::Graph),
+            "health" => Some(Subcommand::Health),
+            _ => None,
+        }
+    }
+}
+
+/// Options parsed from the command line
+#[derive(Debug)]
+pub struct Options {
+    pub subcommand: Subcommand,
+    pub root: PathBuf,
+    pub no_battery: NoBattery,
+    pub no_battery_exit_code: i32,
+    pub format: Format,
+    pub style: Style,
+    /// Interval between refreshes when watching
+    pub watch: Option<Duration>,
+    /// Whether to refresh on power supply uevents when watching
+    pub uevents: bool,
+    pub discovery: Discovery,
+    pub basis: CapacityBasis,
+    pub estimator: Estimator,
+    /// File persisting readings between invocations
+    pub state_file: Option<PathBuf>,
+    pub state_max_age: Duration,
+    /// History log to append to or read from
+    pub log_file: Option<PathBuf>,
+    /// Format of the history log, guessed from its path if unset
+    pub log_format: Option<LogFormat>,
+    pub rotation: Rotation,
+    /// Whether to list peripherals instead of printing the status line
+    pub peripherals: bool,
+    /// Whether to print reports, health and peripherals as JSON
+    pub json: bool,
+    /// Hours of history to chart
+    pub hours: f32,
+    /// Width of the graph, or the terminal width if none
+    pub width: Option<usize>,
+    /// Rows of the graph's charge chart
+    pub height: usize,
+    /// Percentage at or below which to execute the command
+    pub threshold: Option<f32>,
+    /// External command and its arguments
+    pub command: Vec<String>,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parse options from given arguments, excluding the program itself
+    pub fn parse(args: Vec<String>) -> Result<Options, String> {
+        // Sysfs root is taken from flag, then environment, then defaulting to the real sysfs
+        let mut options = Options {
+            subcommand: Subcommand::Status,
+            root: env::var_os(SYSFS_ROOT_ENV)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(PSEUDO_FS_PATH)),
+            no_battery: NoBattery::Empty,
+            no_battery_exit_code: 6,
+            format: Format::Plain,
+            style: Style::default(),
+            watch: None,
+            uevents: true,
+            discovery: Discovery::default(),
+            basis: CapacityBasis::Full,
+            estimator: Estimator::Instant,
+            state_file: None,
+            state_max_age: Duration::from_secs(3600),
+            log_file: None,
+            log_format: None,
+            rotation: Rotation::default(),
+            peripherals: false,
+            json: false,
+            hours: 24f32,
+            width: None,
+            height: 8,
+            threshold: None,
+            command: Vec::new(),
+            help: false,
+        };
+
+        let mut window = None;
+        let mut per_battery = false;
+        let mut layout = PerBattery::default();
+        let mut positionals: Vec<String> = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Options are accepted both as '--flag value' and '--flag=value'
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || inline.clone().or_else(|| args.next()).ok_or(format!("Missing value for {}", flag));
+            match flag.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--sysfs-root" => options.root = PathBuf::from(value()?),
+                "--no-battery" => options.no_battery = NoBattery::parse(&value()?),
+                "--no-battery-exit-code" => {
+                    let code = value()?;
+                    options.no_battery_exit_code = code.parse().map_err(|_| format!("Invalid exit code: {}", code))?;
+                }
+                "--format" => options.format = value()?.parse()?,
+                "--warning" => options.style.thresholds.warning = parse_percentage(&value()?)?,
+                "--critical" => options.style.thresholds.critical = parse_percentage(&value()?)?,
+                "--color-normal" => options.style.colors.normal = parse_color(value()?),
+                "--color-charging" => options.style.colors.charging = parse_color(value()?),
+                "--color-warning" => options.style.colors.warning = parse_color(value()?),
+                "--color-critical" => options.style.colors.critical = parse_color(value()?),
+                "--ramp-charging" => options.style.ramps.charging = parse_ramp(&value()?),
+                "--ramp-discharging" => options.style.ramps.discharging = parse_ramp(&value()?),
+                "--ramp-passive" => options.style.ramps.passive = parse_ramp(&value()?),
+                "--per-battery" => per_battery = true,
+                "--battery-separator" => layout.separator = value()?,
+                "--battery-order" => layout.order = value()?.parse()?,
+                "--include" => options.discovery.include.extend(parse_patterns(&value()?)),
+                "--exclude" => options.discovery.exclude.extend(parse_patterns(&value()?)),
+                "--peripherals" => options.peripherals = true,
+                "--peripheral-icons" => options.discovery.peripherals = true,
+                "--default-threshold" => options.discovery.default_threshold = parse_percentage(&value()?)? / 100f32,
+                "--capacity-basis" => options.basis = value()?.parse()?,
+                "--estimator" => options.estimator = value()?.parse()?,
+                "--estimator-window" => window = Some(parse_interval(&value()?)?),
+                "--state" => {
+                    options.state_file = Some(State::default_path().ok_or("Could not find a state directory, set XDG_STATE_HOME")?)
+                }
+                "--state-file" => options.state_file = Some(PathBuf::from(value()?)),
+                "--state-max-age" => options.state_max_age = parse_interval(&value()?)?,
+                "--no-uevents" => options.uevents = false,
+                "--watch" => options.watch = Some(parse_interval(&value()?)?),
+                "--json" => options.json = true,
+                "--hours" => {
+                    let hours = value()?;
+                    options.hours = hours.parse().map_err(|_| format!("Invalid number of hours: {}", hours))?;
+                }
+                "--width" => options.width = Some(parse_count(&value()?)?),
+                "--height" => options.height = parse_count(&value()?)?,
+                "--log-format" => options.log_format = Some(value()?.parse()?),
+                "--log-max-size" => options.rotation.max_size = parse_size(&value()?)?,
+                "--log-max-age" => options.rotation.max_age = Some(parse_interval(&value()?)?),
+                "--log-keep" => {
+                    let keep = value()?;
+                    options.rotation.keep = keep.parse().map_err(|_| format!("Invalid number of logs: {}", keep))?;
+                }
+                _ if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+                _ => match Subcommand::parse(&arg) {
+                    // A subcommand may only be the first positional
+                    Some(subcommand) if positionals.is_empty() && options.subcommand == Subcommand::Status => {
+                        options.subcommand = subcommand;
+                    }
+                    _ => {
+                        positionals.push(arg);
+                        // Everything after the threshold is the command, including its options
+                        if options.subcommand == Subcommand::Status {
+                            positionals.extend(args);
+                            break;
+                        }
+                    }
+                },
+            }
+        }
+        let mut positionals = positionals.into_iter();
+        match options.subcommand {
+            Subcommand::Status => {
+                if let Some(threshold) = positionals.next() {
+                    options.threshold = Some(threshold.parse().map_err(|_| format!("Could not parse threshold: {}", threshold))?);
+                    options.command = positionals.collect();
+                }
+            }
+            Subcommand::Log | Subcommand::Report | Subcommand::Graph => {
+                options.log_file = positionals.next().map(PathBuf::from);
+                if options.log_file.is_none() {
+                    let directory = State::directory().ok_or("Could not find a state directory, set XDG_STATE_HOME")?;
+                    options.log_file = Some(directory.join("history.jsonl"));
+                }
+                if let Some(extra) = positionals.next() {
+                    return Err(format!("Unexpected argument: {}", extra));
+                }
+            }
+            Subcommand::Health => {
+                if let Some(extra) = positionals.next() {
+                    return Err(format!("Unexpected argument: {}", extra));
+                }
+            }
+        }
+        // The window applies regardless of the order of estimator options
+        if let Some(window) = window {
+            options.estimator = options.estimator.with_window(window);
+        }
+        // Likewise, the layout applies regardless of the order of per-battery options
+        if per_battery {
+            options.style.per_battery = Some(layout);
+        }
+        Ok(options)
+    }
+}
+
+fn parse_percentage(value: &str) -> Result<f32, String> {
+    value.parse().map_err(|_| format!("Could not parse percentage: {}", value))
+}
+
+/// Parse comma-separated name patterns, skipping empty ones
+fn parse_patterns(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|pattern| !pattern.is_empty()).map(String::from).collect()
+}
+
+/// Parse a color, where 'none' leaves the bar's default
+fn parse_color(value: String) -> Option<String> {
+    if value == "none" {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Parse a comma-separated ramp of glyphs
+fn parse_ramp(value: &str) -> Vec<String> {
+    value.split(',').map(str::to_string).collect()
+}
+
+/// Parse an interval of seconds, optionally suffixed by a unit of 'ms', 's', 'm' or 'h'
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid interval: {}", value);
+    let (number, millis) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 1)
+    } else if let Some(number) = value.strip_suffix('s') {
+        (number, 1000)
+    } else if let Some(number) = value.strip_suffix('m') {
+        (number, 60_000)
+    } else if let Some(number) = value.strip_suffix('h') {
+        (number, 3_600_000)
+    } else {
+        (value, 1000)
+    };
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    if number.is_nan() {
+        return Err(invalid());
+    }
+    // Reject intervals rounding down to nothing, e.g. '0.4ms', as well as negative ones
+    match (number * millis as f64) as u64 {
+        0 => Err(invalid()),
+        millis => Ok(Duration::from_millis(millis)),
+    }
+}
+
+/// Parse a size of bytes, optionally suffixed by 'K', 'M' or 'G', where 'none' disables it
+fn parse_size(value: &str) -> Result<Option<u64>, String> {
+    if value == "none" {
+        return Ok(None);
+    }
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 'K')) => (&value[..i], 1024),
+        Some((i, 'M')) => (&value[..i], 1024 * 1024),
+        Some((i, 'G')) => (&value[..i], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    match number.parse::<u64>().ok().and_then(|number| number.checked_mul(multiplier)) {
+        Some(size) => Ok(Some(size)),
+        None => Err(format!("Invalid size: {}", value)),
+    }
+}
+
+/// Parse a positive count, such as a number of characters
+fn parse_count(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("Invalid count: {}", value)),
+    }
+}
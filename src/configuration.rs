@@ -0,0 +1,213 @@
+//! Aggregation of batteries into a single configuration and estimation of remaining time
+
+use crate::battery::{Battery, Status};
+use crate::error::BatteryError;
+use crate::peripheral::Peripheral;
+use crate::sysfs::{get_ac_online, get_peripherals, read_batteries, Discovery};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Capacity that percentages and time-to-full are relative to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacityBasis {
+    /// Capacity when last fully charged, i.e. 'energy_full'
+    Full,
+    /// Capacity as designed, i.e. 'energy_full_design', so wear shows as a lower percentage
+    Design,
+    /// Charge at the stop threshold, i.e. what the battery will charge to, so a battery stopped at
+    /// its threshold shows as full
+    Threshold,
+}
+
+impl fmt::Display for CapacityBasis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CapacityBasis::Full => "full",
+            CapacityBasis::Design => "design",
+            CapacityBasis::Threshold => "threshold",
+        })
+    }
+}
+
+impl FromStr for CapacityBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CapacityBasis, String> {
+        match s {
+            "full" => Ok(CapacityBasis::Full),
+            "design" => Ok(CapacityBasis::Design),
+            "threshold" => Ok(CapacityBasis::Threshold),
+            _ => Err(format!("Unknown capacity basis: {}", s)),
+        }
+    }
+}
+
+/// A configuration of batteries on a given machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct Configuration {
+    /// Time until depleted when discharging, or until threshold when charging
+    pub time_to_completion: Duration,
+    /// Charge across all batteries as a fraction of their capacity on the basis below
+    pub percentage: f32,
+    pub status: Status,
+    pub basis: CapacityBasis,
+    /// Whether external power is connected, or none if there is no adapter to tell
+    pub ac_online: Option<bool>,
+    /// The batteries making up this configuration
+    pub batteries: Vec<Battery>,
+    /// Batteries of peripherals, if read, which are not part of the aggregate
+    pub peripherals: Vec<Peripheral>,
+}
+
+impl Configuration {
+    /// Aggregate given batteries into a configuration, calculating both time-to-completion and percentage
+    pub fn from_batteries(batteries: Vec<Battery>) -> Configuration {
+        let status = calc_status(&batteries);
+        Configuration {
+            time_to_completion: calc_time(&batteries, &status),
+            percentage: calc_percentage(&batteries),
+            status,
+            basis: CapacityBasis::Full,
+            ac_online: None,
+            batteries,
+            peripherals: Vec::new(),
+        }
+    }
+
+    /// Recalculate percentage and time-to-completion relative to capacity on given basis
+    pub fn with_basis(self, basis: CapacityBasis) -> Configuration {
+        let total_draw = calc_draw(&self.batteries, &self.status);
+        Configuration {
+            time_to_completion: calc_time_on_basis(&self.batteries, &self.status, total_draw, basis),
+            percentage: calc_percentage_on_basis(&self.batteries, basis),
+            basis,
+            ..self
+        }
+    }
+
+    /// Net energy flow into all batteries in µW, positive when charging on the whole
+    pub fn net_flow(&self) -> i64 {
+        calc_net_flow(&self.batteries)
+    }
+
+    /// Status resolved to the direction of the net energy flow when mixed
+    pub fn direction(&self) -> Status {
+        calc_direction(&self.batteries, &self.status)
+    }
+
+    /// Charge across all batteries as a fraction of their capacity on given basis, regardless of
+    /// the basis of this configuration
+    pub fn percentage_of(&self, basis: CapacityBasis) -> f32 {
+        calc_percentage_on_basis(&self.batteries, basis)
+    }
+}
+
+/// Find, calculate, and return a configuration of batteries and its values under given sysfs root
+pub fn get_configuration(root: &Path) -> Result<Configuration, BatteryError> {
+    get_configuration_with(root, &Discovery::default())
+}
+
+/// Find, calculate, and return a configuration of batteries under given sysfs root, discovered
+/// using given settings
+pub fn get_configuration_with(root: &Path, discovery: &Discovery) -> Result<Configuration, BatteryError> {
+    // Read adapters once, both for the configuration and for disambiguating battery statuses
+    let ac_online = get_ac_online(root);
+    let batteries = read_batteries(root, discovery, ac_online)?;
+    if batteries.is_empty() {
+        return Err(BatteryError::NoBatteriesFound);
+    }
+    let peripherals = if discovery.peripherals { get_peripherals(root)? } else { Vec::new() };
+    Ok(Configuration { ac_online, peripherals, ..Configuration::from_batteries(batteries) })
+}
+
+/// Find status of all batteries, being mixed when some charge while others discharge
+pub fn calc_status(bats: &[Battery]) -> Status {
+    Status::aggregate(bats.iter().map(|bat| bat.status))
+}
+
+/// Calculate net energy flow into all batteries in µW, positive when charging on the whole
+pub fn calc_net_flow(bats: &[Battery]) -> i64 {
+    bats.iter().map(|bat| bat.net_flow()).sum()
+}
+
+/// Resolve a mixed status of given batteries to the direction of their net energy flow, keeping
+/// any other status
+pub fn calc_direction(bats: &[Battery], stat: &Status) -> Status {
+    match stat {
+        Status::Mixed => match calc_net_flow(bats) {
+            flow if flow > 0 => Status::Charging,
+            flow if flow < 0 => Status::Discharging,
+            _ => Status::Passive,
+        },
+        _ => *stat,
+    }
+}
+
+/// Calculate total power draw in µW, being the magnitude of the net energy flow when mixed
+pub fn calc_draw(bats: &[Battery], stat: &Status) -> u32 {
+    match stat {
+        Status::Mixed => calc_net_flow(bats).unsigned_abs() as u32,
+        _ => bats.iter().map(|x| x.power_draw).sum(),
+    }
+}
+
+/// Calculate time-to-completion based on current values
+pub fn calc_time(bats: &[Battery], stat: &Status) -> Duration {
+    calc_time_with_draw(bats, stat, calc_draw(bats, stat))
+}
+
+/// Calculate time-to-completion based on current charge and given total power draw in µW,
+/// e.g. a smoothed draw rather than the instantaneous one
+pub fn calc_time_with_draw(bats: &[Battery], stat: &Status, total_draw: u32) -> Duration {
+    calc_time_on_basis(bats, stat, total_draw, CapacityBasis::Full)
+}
+
+/// Calculate time-to-completion based on current charge and given total power draw in µW, charging
+/// towards the sum of every battery's own charge target on given basis. A mixed status completes
+/// in the direction of the net energy flow
+pub fn calc_time_on_basis(bats: &[Battery], stat: &Status, total_draw: u32, basis: CapacityBasis) -> Duration {
+    let total_current_charge: u32 = bats.iter().map(|x| x.current_charge).sum();
+    // Each battery stops at its own threshold, however asymmetrical the setup
+    let total_target: u32 = bats.iter().map(|x| x.charge_target(basis)).sum();
+    // Without batteries or power draw, there is no time to estimate
+    if bats.is_empty() || total_draw == 0 {
+        return Duration::new(0, 0);
+    }
+    match calc_direction(bats, stat) {
+        Status::Passive | Status::Waiting | Status::Mixed => {
+            Duration::new(0, 0)
+        }
+        Status::Discharging => {
+            Duration::new((((total_current_charge as f32) / (total_draw as f32)) * 3600f32) as u64, 0)
+        }
+        Status::Charging => {
+            Duration::new(((
+                total_target as f32 - total_current_charge as f32)
+                / (total_draw as f32) * 3600f32) as u64, 0)
+        }
+    }
+}
+
+/// Calculate charge-percentage across all batteries, being zero without any capacity
+pub fn calc_percentage(bats: &[Battery]) -> f32 {
+    calc_percentage_on_basis(bats, CapacityBasis::Full)
+}
+
+/// Calculate charge-percentage across all batteries relative to their capacity on given basis,
+/// being zero without any capacity
+pub fn calc_percentage_on_basis(bats: &[Battery], basis: CapacityBasis) -> f32 {
+    let total_charge: u32 = bats.iter().map(|x| x.capacity(basis)).sum();
+    let total_current_charge: u32 = bats.iter().map(|x| x.current_charge).sum();
+    if total_charge == 0 {
+        return 0f32;
+    }
+
+    let percentage = (total_current_charge as f32) / (total_charge as f32);
+    match basis {
+        // Batteries charged before lowering their threshold are simply full
+        CapacityBasis::Threshold => percentage.min(1f32),
+        _ => percentage,
+    }
+}
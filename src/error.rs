@@ -0,0 +1,72 @@
+//! Errors raised while reading batteries from sysfs
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An error encountered while discovering or reading batteries
+#[derive(Debug)]
+pub enum BatteryError {
+    /// A battery does not expose a required attribute
+    MissingAttribute { battery: String, attribute: String },
+    /// An attribute could not be parsed into the expected type
+    UnparsableValue { battery: String, attribute: String, value: String },
+    /// The 'status' attribute holds a value not known to sysfs
+    UnknownStatus { battery: String, status: String },
+    /// No batteries were found under the sysfs root
+    NoBatteriesFound,
+    /// A file or directory on sysfs could not be read due to its permissions
+    PermissionDenied { path: PathBuf },
+    /// Any other I/O error on a given path
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl BatteryError {
+    /// Convert an I/O error on given path, distinguishing permission errors
+    pub(crate) fn from_io(path: &Path, source: io::Error) -> BatteryError {
+        match source.kind() {
+            io::ErrorKind::PermissionDenied => BatteryError::PermissionDenied { path: path.to_path_buf() },
+            _ => BatteryError::Io { path: path.to_path_buf(), source },
+        }
+    }
+
+    /// Process exit code for this error, distinct per kind of error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BatteryError::MissingAttribute { .. } => 3,
+            BatteryError::UnparsableValue { .. } => 4,
+            BatteryError::UnknownStatus { .. } => 5,
+            BatteryError::NoBatteriesFound => 6,
+            BatteryError::PermissionDenied { .. } => 7,
+            BatteryError::Io { .. } => 8,
+        }
+    }
+}
+
+impl fmt::Display for BatteryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatteryError::MissingAttribute { battery, attribute } => {
+                write!(f, "battery {} has no attribute '{}'", battery, attribute)
+            }
+            BatteryError::UnparsableValue { battery, attribute, value } => {
+                write!(f, "could not parse '{}' of battery {}: '{}'", attribute, battery, value)
+            }
+            BatteryError::UnknownStatus { battery, status } => {
+                write!(f, "could not match status of battery {}: '{}'", battery, status)
+            }
+            BatteryError::NoBatteriesFound => write!(f, "no batteries found"),
+            BatteryError::PermissionDenied { path } => write!(f, "permission denied: {}", path.display()),
+            BatteryError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for BatteryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BatteryError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Smoothed estimation of time-to-completion from a history of samples.
+//!
+//! The instantaneous 'power_now' jumps with load, so the displayed remaining time does too.
+//! Estimators instead derive the power draw from recent samples, either as an exponential moving
+//! average of the draw or as the slope of a linear regression over energy.
+
+use crate::battery::Status;
+use crate::configuration::{calc_draw, calc_time_on_basis, Configuration};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Most samples kept in a history, regardless of their age
+const MAX_SAMPLES: usize = 512;
+
+/// A timestamped reading of a configuration's totals
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Seconds since the Unix epoch
+    pub timestamp: f64,
+    /// Total current energy in µWh
+    pub energy: u32,
+    /// Total power draw in µW, or magnitude of the net energy flow when mixed
+    pub power: u32,
+    pub status: Status,
+}
+
+impl Sample {
+    /// Sample given configuration at given time
+    pub fn new(config: &Configuration, timestamp: f64) -> Sample {
+        Sample {
+            timestamp,
+            energy: config.batteries.iter().map(|bat| bat.current_charge).sum(),
+            power: calc_draw(&config.batteries, &config.status),
+            status: config.status,
+        }
+    }
+
+    /// Sample given configuration now
+    pub fn now(config: &Configuration) -> Sample {
+        Sample::new(config, unix_time())
+    }
+}
+
+/// Current time in seconds since the Unix epoch
+pub fn unix_time() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+/// Recent samples in chronological order, spanning at most a given age
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    pub samples: Vec<Sample>,
+}
+
+impl History {
+    /// Add a sample, dropping samples older than given age relative to it
+    pub fn push(&mut self, sample: Sample, max_age: Duration) {
+        self.samples.push(sample);
+        let oldest = sample.timestamp - max_age.as_secs_f64();
+        self.samples.retain(|s| s.timestamp >= oldest);
+        if self.samples.len() > MAX_SAMPLES {
+            self.samples.drain(..self.samples.len() - MAX_SAMPLES);
+        }
+    }
+
+    /// The trailing run of samples sharing the status of the latest sample, as smoothing across a
+    /// switch between charging and discharging would mix unrelated draws
+    pub fn current_run(&self) -> &[Sample] {
+        let status = match self.samples.last() {
+            Some(sample) => sample.status,
+            None => return &[],
+        };
+        let start = self.samples.iter().rposition(|s| s.status != status).map_or(0, |i| i + 1);
+        &self.samples[start..]
+    }
+}
+
+/// Strategy for deriving the power draw used to estimate time-to-completion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Estimator {
+    /// The current 'power_now', as read
+    Instant,
+    /// An exponential moving average of power draw with given time constant
+    Ema(Duration),
+    /// The slope of a linear regression over energy within given window
+    Regression(Duration),
+}
+
+impl FromStr for Estimator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Estimator, String> {
+        match s {
+            "instant" => Ok(Estimator::Instant),
+            "ema" => Ok(Estimator::Ema(Estimator::DEFAULT_WINDOW)),
+            "regression" => Ok(Estimator::Regression(Estimator::DEFAULT_WINDOW)),
+            _ => Err(format!("Unknown estimator: {}", s)),
+        }
+    }
+}
+
+impl Estimator {
+    /// Default time constant and window of smoothing estimators
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(300);
+
+    /// Replace the time constant or window of a smoothing estimator
+    pub fn with_window(self, window: Duration) -> Estimator {
+        match self {
+            Estimator::Instant => Estimator::Instant,
+            Estimator::Ema(_) => Estimator::Ema(window),
+            Estimator::Regression(_) => Estimator::Regression(window),
+        }
+    }
+
+    /// Age of samples needed by this estimator
+    pub fn window(&self) -> Duration {
+        match self {
+            Estimator::Instant => Duration::new(0, 0),
+            // Samples older than a few time constants hardly weigh in
+            Estimator::Ema(tau) => *tau * 3,
+            Estimator::Regression(window) => *window,
+        }
+    }
+
+    /// Estimate total power draw in µW from given history, falling back to the latest sample's
+    /// instantaneous draw where the history is insufficient
+    pub fn power_draw(&self, history: &History) -> Option<u32> {
+        let run = history.current_run();
+        let latest = run.last()?;
+        let estimate = match self {
+            Estimator::Instant => None,
+            Estimator::Ema(tau) => ema(run, tau.as_secs_f64()),
+            Estimator::Regression(window) => regression(run, latest.timestamp - window.as_secs_f64()),
+        };
+        Some(estimate.unwrap_or(latest.power))
+    }
+
+    /// Estimate time-to-completion of given configuration, whose sample is expected to be the
+    /// latest of given history
+    pub fn estimate(&self, config: &Configuration, history: &History) -> Duration {
+        match self.power_draw(history) {
+            Some(draw) => calc_time_on_basis(&config.batteries, &config.status, draw, config.basis),
+            None => config.time_to_completion,
+        }
+    }
+}
+
+/// Time-weighted exponential moving average of power draw, with given time constant in seconds
+fn ema(run: &[Sample], tau: f64) -> Option<u32> {
+    let (first, rest) = run.split_first()?;
+    if rest.is_empty() || tau <= 0f64 {
+        return None;
+    }
+    let mut average = first.power as f64;
+    let mut previous = first.timestamp;
+    for sample in rest {
+        let alpha = 1f64 - (-(sample.timestamp - previous).max(0f64) / tau).exp();
+        average += alpha * (sample.power as f64 - average);
+        previous = sample.timestamp;
+    }
+    Some(average.round() as u32)
+}
+
+/// Power draw as the absolute slope of a least-squares fit of energy over time, using samples no
+/// older than given timestamp. Gives none unless the fit agrees with the direction of the status
+fn regression(run: &[Sample], oldest: f64) -> Option<u32> {
+    let window: Vec<&Sample> = run.iter().filter(|s| s.timestamp >= oldest).collect();
+    if window.len() < 2 {
+        return None;
+    }
+    let n = window.len() as f64;
+    let mean_t = window.iter().map(|s| s.timestamp).sum::<f64>() / n;
+    let mean_e = window.iter().map(|s| s.energy as f64).sum::<f64>() / n;
+    let covariance: f64 = window.iter().map(|s| (s.timestamp - mean_t) * (s.energy as f64 - mean_e)).sum();
+    let variance: f64 = window.iter().map(|s| (s.timestamp - mean_t).powi(2)).sum();
+    if variance == 0f64 {
+        return None;
+    }
+    // Slope in µWh per second, converted to µW
+    let slope = covariance / variance * 3600f64;
+    let agrees = match window[window.len() - 1].status {
+        Status::Charging => slope > 0f64,
+        Status::Discharging => slope < 0f64,
+        // The energy slope is the net flow, in whichever direction
+        Status::Mixed => slope != 0f64,
+        Status::Passive | Status::Waiting => false,
+    };
+    if agrees {
+        Some(slope.abs().round() as u32)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,344 @@
+//! Formatting of configurations into status-line strings and status-bar protocols
+
+pub mod i3;
+pub mod polybar;
+pub mod template;
+pub mod waybar;
+
+use crate::battery::{Battery, Status};
+use crate::configuration::{calc_time_on_basis, CapacityBasis, Configuration};
+use crate::peripheral::Peripheral;
+use template::Template;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Output format of a rendered configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// A bare status-line string, e.g. '52.10% (-3:05)'
+    Plain,
+    /// A single JSON object per line, for i3blocks' 'format=json'
+    I3blocksJson,
+    /// A block object of the i3bar protocol
+    I3bar,
+    /// A JSON object for waybar custom modules
+    Waybar,
+    /// A ramp icon and status-line string wrapped in '%{F#...}' color tags, for polybar
+    Polybar,
+    /// The same as polybar, whose format tags originate from lemonbar
+    Lemonbar,
+    /// A user-defined template
+    Template(Template),
+}
+
+impl Format {
+    /// Render given configuration in this format
+    pub fn render(&self, config: &Configuration, style: &Style) -> String {
+        match self {
+            Format::Plain => format_line(config, style),
+            Format::I3blocksJson => i3::format_i3blocks(config, style),
+            Format::I3bar => i3::format_i3bar(config, style),
+            Format::Waybar => waybar::format_waybar(config, style),
+            Format::Polybar | Format::Lemonbar => polybar::format_polybar(config, style),
+            Format::Template(template) => template.render(config, style),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "plain" => Ok(Format::Plain),
+            "i3blocks-json" => Ok(Format::I3blocksJson),
+            "i3bar" => Ok(Format::I3bar),
+            "waybar" => Ok(Format::Waybar),
+            "polybar" => Ok(Format::Polybar),
+            "lemonbar" => Ok(Format::Lemonbar),
+            // Anything holding a placeholder is taken as a template
+            _ if s.contains('{') => s.parse().map(Format::Template).map_err(|e: template::TemplateError| e.to_string()),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+/// Severity of a configuration's charge, derived from thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Normal => "normal",
+            Level::Warning => "warning",
+            Level::Critical => "critical",
+        })
+    }
+}
+
+/// Percentages at or below which a configuration not charging is considered low
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    pub warning: f32,
+    pub critical: f32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Thresholds {
+        Thresholds { warning: 30f32, critical: 15f32 }
+    }
+}
+
+impl Thresholds {
+    /// Find level of given configuration. A charging configuration is never considered low
+    pub fn level(&self, config: &Configuration) -> Level {
+        let percentage = config.percentage * 100f32;
+        match config.direction() {
+            Status::Charging => Level::Normal,
+            _ if percentage <= self.critical => Level::Critical,
+            _ if percentage <= self.warning => Level::Warning,
+            _ => Level::Normal,
+        }
+    }
+}
+
+/// Colors, as '#RRGGBB' strings, used by formats supporting them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    /// Color when neither charging nor low, leaving the bar's default if unset
+    pub normal: Option<String>,
+    pub charging: Option<String>,
+    pub warning: Option<String>,
+    pub critical: Option<String>,
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            normal: None,
+            charging: Some("#00FF00".to_string()),
+            warning: Some("#FFFF00".to_string()),
+            critical: Some("#FF0000".to_string()),
+        }
+    }
+}
+
+/// Icon ramps per status, each ordered from empty to full
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ramps {
+    pub charging: Vec<String>,
+    pub discharging: Vec<String>,
+    pub passive: Vec<String>,
+}
+
+impl Default for Ramps {
+    fn default() -> Ramps {
+        // Font Awesome battery glyphs, from empty to full
+        let battery: Vec<String> = ["\u{f244}", "\u{f243}", "\u{f242}", "\u{f241}", "\u{f240}"]
+            .iter()
+            .map(|glyph| glyph.to_string())
+            .collect();
+        Ramps {
+            // Font Awesome plug glyph
+            charging: vec!["\u{f1e6}".to_string()],
+            discharging: battery.clone(),
+            passive: battery,
+        }
+    }
+}
+
+impl Ramps {
+    /// Pick the glyph of given configuration's status ramp by its charge level
+    pub fn icon(&self, config: &Configuration) -> &str {
+        self.glyph(config.direction(), config.percentage)
+    }
+
+    /// Pick the glyph of given status' ramp by given charge as a fraction
+    pub fn glyph(&self, status: Status, fraction: f32) -> &str {
+        let ramp = match status {
+            Status::Charging => &self.charging,
+            Status::Discharging => &self.discharging,
+            Status::Passive | Status::Waiting | Status::Mixed => &self.passive,
+        };
+        if ramp.is_empty() {
+            return "";
+        }
+        // Spread glyphs evenly, with a full charge landing on the last glyph
+        let index = (fraction.max(0f32) * ramp.len() as f32) as usize;
+        &ramp[index.min(ramp.len() - 1)]
+    }
+}
+
+/// Order of batteries in per-battery output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatteryOrder {
+    /// By name, e.g. 'BAT0' before 'BAT1'
+    Name,
+    /// Lowest charge first
+    Percentage,
+    /// Given names first, in this order, followed by any other batteries by name
+    Names(Vec<String>),
+}
+
+impl FromStr for BatteryOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BatteryOrder, String> {
+        match s {
+            "name" => Ok(BatteryOrder::Name),
+            "percentage" => Ok(BatteryOrder::Percentage),
+            _ if s.is_empty() => Err("Empty battery order".to_string()),
+            _ => Ok(BatteryOrder::Names(s.split(',').map(|name| name.trim().to_string()).collect())),
+        }
+    }
+}
+
+/// Layout listing every battery separately instead of their aggregate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerBattery {
+    /// Text between batteries
+    pub separator: String,
+    pub order: BatteryOrder,
+}
+
+impl Default for PerBattery {
+    fn default() -> PerBattery {
+        PerBattery { separator: " | ".to_string(), order: BatteryOrder::Name }
+    }
+}
+
+impl PerBattery {
+    /// Batteries of given configuration in this order
+    pub fn batteries<'a>(&self, config: &'a Configuration) -> Vec<&'a Battery> {
+        let mut batteries: Vec<&Battery> = config.batteries.iter().collect();
+        match &self.order {
+            // Configurations hold their batteries by name already
+            BatteryOrder::Name => {}
+            BatteryOrder::Percentage => {
+                batteries.sort_by(|a, b| a.percentage_of(config.basis).total_cmp(&b.percentage_of(config.basis)))
+            }
+            BatteryOrder::Names(names) => {
+                batteries.sort_by_key(|bat| names.iter().position(|name| *name == bat.name).unwrap_or(names.len()))
+            }
+        }
+        batteries
+    }
+}
+
+/// Appearance of formats supporting colors, urgency and icons
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    pub thresholds: Thresholds,
+    pub colors: Colors,
+    pub ramps: Ramps,
+    /// Layout listing every battery, or none for the aggregate
+    pub per_battery: Option<PerBattery>,
+}
+
+impl Style {
+    /// Color of given configuration, derived from its status and level
+    pub fn color(&self, config: &Configuration) -> Option<&str> {
+        let color = match (config.direction(), self.thresholds.level(config)) {
+            (Status::Charging, _) => &self.colors.charging,
+            (_, Level::Critical) => &self.colors.critical,
+            (_, Level::Warning) => &self.colors.warning,
+            (_, Level::Normal) => &self.colors.normal,
+        };
+        color.as_deref()
+    }
+
+    /// Whether given configuration is critically low while discharging
+    pub fn urgent(&self, config: &Configuration) -> bool {
+        config.direction() == Status::Discharging && self.thresholds.level(config) == Level::Critical
+    }
+}
+
+/// Format a status-line string, e.g. '52.10% (-3:05)', followed by the batteries charging and
+/// discharging when mixed, e.g. '50.00% (+5:00) +BAT0 -BAT1'
+pub fn format_status(config: &Configuration) -> String {
+    // Format percentage as an actual percentage and calculate pretty display-time
+    let status = format!("{:.2}%{}", config.percentage * 100f32, calc_display_time(config.direction(), config.time_to_completion));
+    if config.status != Status::Mixed {
+        return status;
+    }
+    let active: Vec<String> = config
+        .batteries
+        .iter()
+        .filter_map(|bat| match bat.status {
+            Status::Charging => Some(format!("+{}", bat.name)),
+            Status::Discharging => Some(format!("-{}", bat.name)),
+            _ => None,
+        })
+        .collect();
+    format!("{} {}", status, active.join(" "))
+}
+
+/// Format a status-line string of a single battery with its percentage on given basis, e.g.
+/// 'BAT1 12% (-1:40)'
+pub fn format_battery_status(bat: &Battery, basis: CapacityBasis) -> String {
+    let time = calc_time_on_basis(std::slice::from_ref(bat), &bat.status, bat.power_draw, basis);
+    format!("{} {:.0}%{}", bat.name, bat.percentage_of(basis) * 100f32, calc_display_time(bat.status, time))
+}
+
+/// Format the status-line string of given configuration in given style, listing every battery when
+/// per-battery and the aggregate otherwise, followed by any peripherals
+pub fn format_line(config: &Configuration, style: &Style) -> String {
+    let line = match &style.per_battery {
+        Some(per_battery) => per_battery
+            .batteries(config)
+            .iter()
+            .map(|bat| format_battery_status(bat, config.basis))
+            .collect::<Vec<_>>()
+            .join(&per_battery.separator),
+        None => format_status(config),
+    };
+    if config.peripherals.is_empty() {
+        line
+    } else {
+        format!("{}  {}", line, format_peripherals(&config.peripherals, style))
+    }
+}
+
+/// Format a compact list of peripherals as ramp glyphs with their charge, marking those low with
+/// '!', e.g. '\u{f242}85% \u{f244}5%!'
+pub fn format_peripherals(peripherals: &[Peripheral], style: &Style) -> String {
+    peripherals
+        .iter()
+        .map(|peripheral| {
+            let status = peripheral.status.unwrap_or(Status::Passive);
+            let glyph = peripheral.fraction().map_or("", |fraction| style.ramps.glyph(status, fraction));
+            let warning = if peripheral.level(&style.thresholds) == Level::Normal { "" } else { "!" };
+            format!("{}{}{}", glyph, peripheral.charge(), warning)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Format a short status-line string of the rounded percentage, e.g. '52%'
+pub fn format_short_status(config: &Configuration) -> String {
+    format!("{:.0}%", config.percentage * 100f32)
+}
+
+/// Calculate display-time and format display-string according to status
+pub fn calc_display_time(status: Status, time: Duration) -> String {
+    // Calculate hours and minutes for printing
+    let hours = time.as_secs() / 3600;
+    let minutes = time.as_secs() % 3600 / 60;
+    // Match on status and format string accordingly with {+, -}, printing empty when irrelevant
+    match status {
+        Status::Charging => {
+            format!(" (+{}:{:02})", hours, minutes)
+        }
+        Status::Discharging => {
+            format!(" (-{}:{:02})", hours, minutes)
+        }
+        // A mixed status is to be resolved into its direction beforehand
+        Status::Passive | Status::Waiting | Status::Mixed => { "".to_string() }
+    }
+}
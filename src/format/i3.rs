@@ -0,0 +1,40 @@
+//! JSON blocks for i3blocks and the i3bar protocol
+
+use super::{format_line, format_short_status, Style};
+use crate::configuration::Configuration;
+use serde::Serialize;
+
+/// Name identifying the block in the i3bar protocol
+const BLOCK_NAME: &str = "battery";
+
+/// A block as understood by both i3blocks and i3bar
+#[derive(Serialize)]
+struct Block<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    full_text: String,
+    short_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<&'a str>,
+    urgent: bool,
+}
+
+fn block<'a>(config: &Configuration, style: &'a Style, name: Option<&'a str>) -> Block<'a> {
+    Block {
+        name,
+        full_text: format_line(config, style),
+        short_text: format_short_status(config),
+        color: style.color(config),
+        urgent: style.urgent(config),
+    }
+}
+
+/// Format a JSON object for i3blocks' 'format=json'
+pub fn format_i3blocks(config: &Configuration, style: &Style) -> String {
+    serde_json::to_string(&block(config, style, None)).unwrap()
+}
+
+/// Format a named block object of the i3bar protocol
+pub fn format_i3bar(config: &Configuration, style: &Style) -> String {
+    serde_json::to_string(&block(config, style, Some(BLOCK_NAME))).unwrap()
+}
@@ -0,0 +1,14 @@
+//! Status-line strings with format tags for polybar and lemonbar
+
+use super::{format_line, Style};
+use crate::configuration::Configuration;
+
+/// Format a ramp icon and status-line string, wrapped in a '%{F#...}' color tag when the
+/// configuration has a color, e.g. '%{F#FF0000} 10.00% (-0:30)%{F-}'
+pub fn format_polybar(config: &Configuration, style: &Style) -> String {
+    let text = format!("{} {}", style.ramps.icon(config), format_line(config, style));
+    match style.color(config) {
+        Some(color) => format!("%{{F{}}}{}%{{F-}}", color, text),
+        None => text,
+    }
+}
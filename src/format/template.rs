@@ -0,0 +1,348 @@
+//! User-defined output templates.
+//!
+//! A template is literal text with placeholders in braces, such as `{percent:.0}%`, and
+//! conditional sections in square brackets, such as `[ ({sign}{time})]`, which disappear
+//! entirely when any placeholder inside them is unavailable. Per-battery values are addressed by
+//! the lowercase battery name, as in `{bat0.percent}`. Literal braces and brackets are written
+//! doubled, e.g. `{{` for `{`.
+
+use super::{format_peripherals, Style};
+use crate::battery::{Battery, Status};
+use crate::configuration::{CapacityBasis, Configuration};
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed output template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+    /// Rendered only when all of its placeholders are available
+    Section(Vec<Segment>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placeholder {
+    /// Lowercase name of the battery addressed, or the aggregated configuration if none
+    battery: Option<String>,
+    field: Field,
+    precision: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// Charge percentage on the configured capacity basis
+    Percent,
+    /// Charge percentage of the capacity when last fully charged
+    PercentFull,
+    /// Charge percentage of the design capacity
+    PercentDesign,
+    /// Charge percentage of the charge at the stop threshold
+    PercentThreshold,
+    /// Time-to-completion as 'h:mm'
+    Time,
+    /// '+' when charging and '-' when discharging
+    Sign,
+    Status,
+    StatusIcon,
+    /// Power draw in W
+    PowerW,
+    /// Signed energy flow in W, positive when charging
+    FlowW,
+    /// Names of the batteries charging or discharging
+    Active,
+    /// 'online' or 'offline' by whether external power is connected
+    Ac,
+    /// Compact list of peripherals
+    Peripherals,
+    /// Current energy in Wh
+    EnergyWh,
+    /// Charge threshold percentage
+    Threshold,
+}
+
+impl Field {
+    fn parse(name: &str, battery: bool) -> Option<Field> {
+        let field = match name {
+            "percent" => Field::Percent,
+            "percent_full" => Field::PercentFull,
+            "percent_design" => Field::PercentDesign,
+            "percent_threshold" => Field::PercentThreshold,
+            "time" => Field::Time,
+            "sign" => Field::Sign,
+            "status" => Field::Status,
+            "status_icon" => Field::StatusIcon,
+            "power_w" => Field::PowerW,
+            "flow_w" => Field::FlowW,
+            "active" => Field::Active,
+            "ac" => Field::Ac,
+            "peripherals" => Field::Peripherals,
+            "energy_wh" => Field::EnergyWh,
+            "threshold" => Field::Threshold,
+            _ => return None,
+        };
+        // Fields available per battery and for the aggregate respectively
+        let valid = match field {
+            Field::Percent
+            | Field::PercentFull
+            | Field::PercentDesign
+            | Field::PercentThreshold
+            | Field::Status
+            | Field::PowerW
+            | Field::FlowW
+            | Field::EnergyWh => true,
+            Field::Threshold => battery,
+            Field::Time | Field::Sign | Field::StatusIcon | Field::Active | Field::Ac | Field::Peripherals => !battery,
+        };
+        if valid {
+            Some(field)
+        } else {
+            None
+        }
+    }
+
+    fn numeric(&self) -> bool {
+        matches!(
+            self,
+            Field::Percent
+                | Field::PercentFull
+                | Field::PercentDesign
+                | Field::PercentThreshold
+                | Field::PowerW
+                | Field::FlowW
+                | Field::EnergyWh
+                | Field::Threshold
+        )
+    }
+}
+
+/// An error in the syntax of a template, at a given character position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid template at position {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+fn error<T>(position: usize, message: String) -> Result<T, TemplateError> {
+    Err(TemplateError { position, message })
+}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Template, TemplateError> {
+        let chars: Vec<char> = s.chars().collect();
+        // Segments of the top level, and of the currently open section if any
+        let mut segments = Vec::new();
+        let mut section: Option<(usize, Vec<Segment>)> = None;
+        let mut literal = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let doubled = chars.get(i + 1) == Some(&c);
+            match c {
+                '{' | '}' | '[' | ']' if doubled => {
+                    literal.push(c);
+                    i += 2;
+                    continue;
+                }
+                '{' => {
+                    let end = match chars[i..].iter().position(|&c| c == '}') {
+                        Some(end) => i + end,
+                        None => return error(i, "unclosed placeholder".to_string()),
+                    };
+                    let content: String = chars[i + 1..end].iter().collect();
+                    let placeholder = parse_placeholder(&content, i)?;
+                    flush(&mut literal, &mut section, &mut segments);
+                    push(Segment::Placeholder(placeholder), &mut section, &mut segments);
+                    i = end + 1;
+                    continue;
+                }
+                '}' => return error(i, "unmatched '}'".to_string()),
+                '[' => {
+                    if section.is_some() {
+                        return error(i, "sections cannot be nested".to_string());
+                    }
+                    flush(&mut literal, &mut section, &mut segments);
+                    section = Some((i, Vec::new()));
+                }
+                ']' => {
+                    flush(&mut literal, &mut section, &mut segments);
+                    match section.take() {
+                        Some((_, inner)) => segments.push(Segment::Section(inner)),
+                        None => return error(i, "unmatched ']'".to_string()),
+                    }
+                }
+                _ => literal.push(c),
+            }
+            i += 1;
+        }
+        if let Some((start, _)) = section {
+            return error(start, "unclosed section".to_string());
+        }
+        flush(&mut literal, &mut section, &mut segments);
+        Ok(Template { segments })
+    }
+}
+
+/// Push a segment onto the open section, or the top level if none
+fn push(segment: Segment, section: &mut Option<(usize, Vec<Segment>)>, segments: &mut Vec<Segment>) {
+    match section {
+        Some((_, inner)) => inner.push(segment),
+        None => segments.push(segment),
+    }
+}
+
+/// Push any pending literal text as a segment
+fn flush(literal: &mut String, section: &mut Option<(usize, Vec<Segment>)>, segments: &mut Vec<Segment>) {
+    if !literal.is_empty() {
+        push(Segment::Literal(std::mem::take(literal)), section, segments);
+    }
+}
+
+/// Parse the content of a placeholder, e.g. 'bat0.percent:.0'
+fn parse_placeholder(content: &str, position: usize) -> Result<Placeholder, TemplateError> {
+    let (name, spec) = match content.split_once(':') {
+        Some((name, spec)) => (name, Some(spec)),
+        None => (content, None),
+    };
+    let (battery, field_name) = match name.split_once('.') {
+        Some((battery, field)) => (Some(battery.to_lowercase()), field),
+        None => (None, name),
+    };
+    let field = match Field::parse(field_name, battery.is_some()) {
+        Some(field) => field,
+        None => return error(position, format!("unknown placeholder '{{{}}}'", name)),
+    };
+    let precision = match spec {
+        None => None,
+        Some(spec) => {
+            if !field.numeric() {
+                return error(position, format!("'{{{}}}' is not numeric and takes no precision", name));
+            }
+            match spec.strip_prefix('.').and_then(|p| p.parse().ok()) {
+                Some(precision) => Some(precision),
+                None => return error(position, format!("invalid precision '{}', expected e.g. '.2'", spec)),
+            }
+        }
+    };
+    Ok(Placeholder { battery, field, precision })
+}
+
+impl Template {
+    /// Render given configuration, leaving out unavailable values and their sections
+    pub fn render(&self, config: &Configuration, style: &Style) -> String {
+        let mut output = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => output.push_str(text),
+                Segment::Placeholder(placeholder) => {
+                    output.push_str(&placeholder.value(config, style).unwrap_or_default());
+                }
+                Segment::Section(inner) => {
+                    let values: Option<Vec<String>> = inner
+                        .iter()
+                        .map(|segment| match segment {
+                            Segment::Placeholder(placeholder) => placeholder.value(config, style),
+                            Segment::Literal(text) => Some(text.clone()),
+                            Segment::Section(_) => None,
+                        })
+                        .collect();
+                    output.push_str(&values.unwrap_or_default().concat());
+                }
+            }
+        }
+        output
+    }
+}
+
+impl Placeholder {
+    /// Value of this placeholder for given configuration, if available
+    fn value(&self, config: &Configuration, style: &Style) -> Option<String> {
+        let number = |value: f32| Some(format!("{:.*}", self.precision.unwrap_or(2), value));
+        match &self.battery {
+            Some(name) => {
+                let bat = config.batteries.iter().find(|bat| bat.name.to_lowercase() == *name)?;
+                match self.field {
+                    Field::Percent => number(bat.percentage_of(config.basis) * 100f32),
+                    Field::PercentFull => number(bat.percentage_of(CapacityBasis::Full) * 100f32),
+                    Field::PercentDesign => number(bat.percentage_of(CapacityBasis::Design) * 100f32),
+                    Field::PercentThreshold => number(bat.percentage_of(CapacityBasis::Threshold) * 100f32),
+                    Field::Status => Some(bat.status.to_string()),
+                    Field::PowerW => number(watts(bat.power_draw)),
+                    Field::FlowW => number(bat.net_flow() as f32 / 1_000_000f32),
+                    Field::EnergyWh => number(watts(bat.current_charge)),
+                    Field::Threshold => number(bat.tlp_threshold * 100f32),
+                    _ => None,
+                }
+            }
+            None => match self.field {
+                Field::Percent => number(config.percentage * 100f32),
+                Field::PercentFull => number(config.percentage_of(CapacityBasis::Full) * 100f32),
+                Field::PercentDesign => number(config.percentage_of(CapacityBasis::Design) * 100f32),
+                Field::PercentThreshold => number(config.percentage_of(CapacityBasis::Threshold) * 100f32),
+                Field::Time => {
+                    if matches!(config.direction(), Status::Passive | Status::Waiting) {
+                        return None;
+                    }
+                    let secs = config.time_to_completion.as_secs();
+                    Some(format!("{}:{:02}", secs / 3600, secs % 3600 / 60))
+                }
+                Field::Sign => match config.direction() {
+                    Status::Charging => Some("+".to_string()),
+                    Status::Discharging => Some("-".to_string()),
+                    Status::Passive | Status::Waiting | Status::Mixed => None,
+                },
+                Field::Status => Some(config.status.to_string()),
+                Field::StatusIcon => Some(style.ramps.icon(config).to_string()),
+                Field::PowerW => number(watts(total(&config.batteries, |bat| bat.power_draw))),
+                Field::FlowW => number(config.net_flow() as f32 / 1_000_000f32),
+                Field::Active => {
+                    let active: Vec<&str> = config
+                        .batteries
+                        .iter()
+                        .filter(|bat| matches!(bat.status, Status::Charging | Status::Discharging))
+                        .map(|bat| bat.name.as_str())
+                        .collect();
+                    if active.is_empty() {
+                        None
+                    } else {
+                        Some(active.join(","))
+                    }
+                }
+                Field::EnergyWh => number(watts(total(&config.batteries, |bat| bat.current_charge))),
+                Field::Ac => config.ac_online.map(|online| if online { "online" } else { "offline" }.to_string()),
+                Field::Peripherals => {
+                    if config.peripherals.is_empty() {
+                        None
+                    } else {
+                        Some(format_peripherals(&config.peripherals, style))
+                    }
+                }
+                Field::Threshold => None,
+            },
+        }
+    }
+}
+
+fn total(bats: &[Battery], value: fn(&Battery) -> u32) -> u32 {
+    bats.iter().map(value).sum()
+}
+
+/// Convert a µW or µWh value to W or Wh respectively
+fn watts(micros: u32) -> f32 {
+    micros as f32 / 1_000_000f32
+}
@@ -0,0 +1,90 @@
+//! JSON for waybar custom modules
+
+use super::{format_line, Level, Style};
+use crate::battery::{Battery, Status};
+use crate::configuration::{calc_time_on_basis, CapacityBasis, Configuration};
+use serde::Serialize;
+
+/// Output of a waybar custom module with 'return-type: json'
+#[derive(Serialize)]
+struct Module<'a> {
+    text: String,
+    tooltip: String,
+    class: String,
+    percentage: u32,
+    alt: String,
+    percentage_full: u32,
+    percentage_design: u32,
+    percentage_threshold: u32,
+    /// Whether external power is connected, absent without any adapter to tell
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ac_online: Option<bool>,
+    /// Every battery in per-battery order, only when per-battery
+    #[serde(skip_serializing_if = "Option::is_none")]
+    batteries: Option<Vec<BatteryModule<'a>>>,
+}
+
+/// A single battery of a per-battery module
+#[derive(Serialize)]
+struct BatteryModule<'a> {
+    name: &'a str,
+    percentage: u32,
+    status: Status,
+    threshold: u32,
+    /// Time-to-completion as 'h:mm', absent when passive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+}
+
+fn battery_module(bat: &Battery, basis: CapacityBasis) -> BatteryModule<'_> {
+    let time = match bat.status {
+        Status::Charging | Status::Discharging => {
+            let secs = calc_time_on_basis(std::slice::from_ref(bat), &bat.status, bat.power_draw, basis).as_secs();
+            Some(format!("{}:{:02}", secs / 3600, secs % 3600 / 60))
+        }
+        _ => None,
+    };
+    BatteryModule {
+        name: &bat.name,
+        percentage: (bat.percentage_of(basis) * 100f32).round() as u32,
+        status: bat.status,
+        threshold: (bat.tlp_threshold * 100f32).round() as u32,
+        time,
+    }
+}
+
+/// Describe a single battery for the tooltip, e.g. 'BAT0: 52.10%, 8.25 W, threshold 80%'
+fn format_battery(bat: &Battery, basis: CapacityBasis) -> String {
+    format!(
+        "{}: {:.2}%, {:.2} W, threshold {:.0}%",
+        bat.name,
+        bat.percentage_of(basis) * 100f32,
+        bat.power_draw as f32 / 1_000_000f32,
+        bat.tlp_threshold * 100f32
+    )
+}
+
+/// Format a JSON object for a waybar custom module, with 'class' reflecting status and
+/// critical charge, a tooltip listing every battery, and when per-battery, every battery's values
+pub fn format_waybar(config: &Configuration, style: &Style) -> String {
+    let class = match (config.status, style.thresholds.level(config)) {
+        (Status::Charging, _) => "charging".to_string(),
+        (_, Level::Critical) => "critical".to_string(),
+        (status, _) => status.to_string(),
+    };
+    let module = Module {
+        text: format_line(config, style),
+        tooltip: config.batteries.iter().map(|bat| format_battery(bat, config.basis)).collect::<Vec<_>>().join("\n"),
+        class,
+        percentage: (config.percentage * 100f32).round() as u32,
+        alt: config.status.to_string(),
+        percentage_full: (config.percentage_of(CapacityBasis::Full) * 100f32).round() as u32,
+        percentage_design: (config.percentage_of(CapacityBasis::Design) * 100f32).round() as u32,
+        percentage_threshold: (config.percentage_of(CapacityBasis::Threshold) * 100f32).round() as u32,
+        ac_online: config.ac_online,
+        batteries: style.per_battery.as_ref().map(|per_battery| {
+            per_battery.batteries(config).into_iter().map(|bat| battery_module(bat, config.basis)).collect()
+        }),
+    };
+    serde_json::to_string(&module).unwrap()
+}
@@ -0,0 +1,132 @@
+//! Terminal charts of logged charge and power draw
+
+use crate::battery::Status;
+use crate::log::LogEntry;
+use std::fmt::Write;
+
+/// Block elements from empty to full, in eighths
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Width of the labels left of each chart
+const GUTTER: usize = 7;
+
+/// Averaged readings within one column of a chart
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Column {
+    pub percentage: f32,
+    /// Unit: W
+    pub power: f32,
+    /// Charging if charging at any point, else mixed or discharging if so at any point, else passive
+    pub status: Status,
+}
+
+/// Dimensions of a rendered chart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    /// Total width in characters, including labels
+    pub width: usize,
+    /// Rows of the percentage chart, with the power chart taking half as many
+    pub height: usize,
+}
+
+/// Split the time between start and end into given number of columns, but at least one,
+/// averaging the entries of each, where columns without entries are none
+pub fn bin(entries: &[LogEntry], start: u64, end: u64, columns: usize) -> Vec<Option<Column>> {
+    let columns = columns.max(1);
+    let span = end.saturating_sub(start).max(1);
+    let mut sums = vec![(0f32, 0f32, 0usize, Status::Passive); columns];
+    for entry in entries.iter().filter(|entry| entry.timestamp >= start && entry.timestamp <= end) {
+        let index = (((entry.timestamp - start) as f64 / span as f64 * columns as f64) as usize).min(columns - 1);
+        let sum = &mut sums[index];
+        sum.0 += entry.percentage;
+        sum.1 += entry.power() as f32 / 1_000_000f32;
+        sum.2 += 1;
+        sum.3 = match (sum.3, entry.status) {
+            (Status::Charging, _) | (_, Status::Charging) => Status::Charging,
+            (Status::Mixed, _) | (_, Status::Mixed) => Status::Mixed,
+            (Status::Discharging, _) | (_, Status::Discharging) => Status::Discharging,
+            _ => Status::Passive,
+        };
+    }
+    sums.into_iter()
+        .map(|(percentage, power, count, status)| {
+            if count == 0 {
+                None
+            } else {
+                Some(Column { percentage: percentage / count as f32, power: power / count as f32, status })
+            }
+        })
+        .collect()
+}
+
+/// Rows of a bar chart of given values relative to given maximum, top row first
+fn bars(values: &[Option<f32>], max: f32, height: usize) -> Vec<String> {
+    let levels: Vec<usize> = values
+        .iter()
+        .map(|value| match value {
+            Some(value) if max > 0f32 => ((value / max).clamp(0f32, 1f32) * (height * 8) as f32).round() as usize,
+            _ => 0,
+        })
+        .collect();
+    (0..height)
+        .rev()
+        .map(|row| levels.iter().map(|level| BLOCKS[level.saturating_sub(row * 8).min(8)]).collect())
+        .collect()
+}
+
+/// Render a chart of percentage and power draw between start and end, with a strip marking
+/// charging columns '+', discharging columns '-', mixed columns '±' and passive columns '·' beneath
+pub fn render(entries: &[LogEntry], start: u64, end: u64, size: Size) -> String {
+    let columns = size.width.saturating_sub(GUTTER).max(1);
+    let height = size.height.max(1);
+    let bins = bin(entries, start, end, columns);
+    let max_power = bins.iter().flatten().map(|column| column.power).fold(0f32, f32::max);
+
+    let mut chart = String::new();
+    let percentages: Vec<Option<f32>> = bins.iter().map(|bin| bin.map(|column| column.percentage)).collect();
+    for (i, row) in bars(&percentages, 100f32, height).iter().enumerate() {
+        let label = match i {
+            0 => "100%".to_string(),
+            _ if i == height - 1 => "0%".to_string(),
+            _ => String::new(),
+        };
+        writeln!(chart, "{:>5} │{}", label, row).unwrap();
+    }
+    let status: String = bins
+        .iter()
+        .map(|bin| match bin.map(|column| column.status) {
+            Some(Status::Charging) => '+',
+            Some(Status::Discharging) => '-',
+            Some(Status::Mixed) => '±',
+            Some(Status::Passive) | Some(Status::Waiting) => '·',
+            None => ' ',
+        })
+        .collect();
+    writeln!(chart, "{:>5} │{}", "", status).unwrap();
+
+    let powers: Vec<Option<f32>> = bins.iter().map(|bin| bin.map(|column| column.power)).collect();
+    let power_height = (height / 2).max(1);
+    for (i, row) in bars(&powers, max_power, power_height).iter().enumerate() {
+        let label = if i == 0 { format!("{:.0}W", max_power) } else { String::new() };
+        writeln!(chart, "{:>5} │{}", label, row).unwrap();
+    }
+
+    let hours = end.saturating_sub(start) as f32 / 3600f32;
+    let since = format!("-{}h", hours.round());
+    writeln!(chart, "{:>5}  {}{:>width$}", "", since, "now", width = columns.saturating_sub(since.len()).max(" now".len())).unwrap();
+    chart
+}
+
+/// Width of the terminal on standard output, from '$COLUMNS' or the terminal itself
+pub fn terminal_width() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()) {
+        return Some(columns);
+    }
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if result == 0 && size.ws_col > 0 {
+        Some(size.ws_col as usize)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,125 @@
+//! Wear of batteries relative to their design capacity
+
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Capacity and identification of a single battery
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatteryHealth {
+    pub name: String,
+    /// Capacity when last fully charged, unit: µWh
+    pub energy_full: u32,
+    /// Capacity as designed, if reported, unit: µWh
+    pub energy_full_design: Option<u32>,
+    pub cycle_count: Option<u32>,
+    pub manufacturer: Option<String>,
+    pub model_name: Option<String>,
+    pub technology: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+impl BatteryHealth {
+    /// Percentage of the design capacity still available, if the design capacity is known
+    pub fn health(&self) -> Option<f32> {
+        match self.energy_full_design {
+            Some(design) if design > 0 => Some(self.energy_full as f32 / design as f32 * 100f32),
+            _ => None,
+        }
+    }
+
+    /// Percentage of the design capacity lost, if the design capacity is known
+    pub fn wear(&self) -> Option<f32> {
+        self.health().map(|health| (100f32 - health).max(0f32))
+    }
+}
+
+/// Percentage of the combined design capacity still available, over batteries reporting one
+pub fn aggregate_health(batteries: &[BatteryHealth]) -> Option<f32> {
+    let (full, design) = batteries
+        .iter()
+        .filter_map(|battery| battery.energy_full_design.map(|design| (battery.energy_full as f64, design as f64)))
+        .fold((0f64, 0f64), |(full, design), (f, d)| (full + f, design + d));
+    if design > 0f64 {
+        Some((full / design * 100f64) as f32)
+    } else {
+        None
+    }
+}
+
+/// Format an optional percentage, or '-' if unknown
+fn format_percentage(percentage: Option<f32>) -> String {
+    percentage.map_or_else(|| "-".to_string(), |percentage| format!("{:.2}%", percentage))
+}
+
+/// Format an optional energy in µWh as Wh, or '-' if unknown
+fn format_energy(energy: Option<u32>) -> String {
+    energy.map_or_else(|| "-".to_string(), |energy| format!("{:.2} Wh", energy as f32 / 1_000_000f32))
+}
+
+/// Format batteries as a table with one row per battery, followed by a total if there are several
+pub fn format_table(batteries: &[BatteryHealth]) -> String {
+    let mut table = String::from("battery   health     wear       full     design  cycles  manufacturer  model  technology  serial\n");
+    for battery in batteries {
+        let text = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        writeln!(
+            table,
+            "{:<7}  {:>7}  {:>7}  {:>9}  {:>9}  {:>6}  {}  {}  {}  {}",
+            battery.name,
+            format_percentage(battery.health()),
+            format_percentage(battery.wear()),
+            format_energy(Some(battery.energy_full)),
+            format_energy(battery.energy_full_design),
+            battery.cycle_count.map_or_else(|| "-".to_string(), |count| count.to_string()),
+            text(&battery.manufacturer),
+            text(&battery.model_name),
+            text(&battery.technology),
+            text(&battery.serial_number)
+        )
+        .unwrap();
+    }
+    if batteries.len() > 1 {
+        let health = aggregate_health(batteries);
+        let full = batteries.iter().map(|battery| battery.energy_full).sum();
+        let design = batteries.iter().map(|battery| battery.energy_full_design).sum::<Option<u32>>();
+        writeln!(
+            table,
+            "{:<7}  {:>7}  {:>7}  {:>9}  {:>9}",
+            "total",
+            format_percentage(health),
+            format_percentage(health.map(|health| (100f32 - health).max(0f32))),
+            format_energy(Some(full)),
+            format_energy(design)
+        )
+        .unwrap();
+    }
+    table
+}
+
+#[derive(Serialize)]
+struct HealthJson<'a> {
+    batteries: Vec<BatteryJson<'a>>,
+    health: Option<f32>,
+    wear: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct BatteryJson<'a> {
+    #[serde(flatten)]
+    battery: &'a BatteryHealth,
+    health: Option<f32>,
+    wear: Option<f32>,
+}
+
+/// Format batteries as a JSON object holding them along with their aggregated health and wear
+pub fn format_json(batteries: &[BatteryHealth]) -> String {
+    let health = aggregate_health(batteries);
+    let json = HealthJson {
+        batteries: batteries
+            .iter()
+            .map(|battery| BatteryJson { battery, health: battery.health(), wear: battery.wear() })
+            .collect(),
+        health,
+        wear: health.map(|health| (100f32 - health).max(0f32)),
+    };
+    serde_json::to_string_pretty(&json).unwrap()
+}
@@ -0,0 +1,51 @@
+//! Status-bar battery information for multi-battery systems on Linux.
+//!
+//! Batteries are discovered on sysfs and normalized into [`Battery`] readings, which are
+//! aggregated into a single [`Configuration`] holding the overall percentage, status, and
+//! time-to-completion. A configuration is formatted into a status-line string with
+//! [`format_status`].
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use poly_battery_status::{format_status, get_configuration, PSEUDO_FS_PATH};
+//!
+//! let config = get_configuration(Path::new(PSEUDO_FS_PATH))?;
+//! println!("{}", format_status(&config));
+//! # Ok::<(), poly_battery_status::BatteryError>(())
+//! ```
+
+pub mod battery;
+pub mod configuration;
+pub mod error;
+pub mod estimate;
+pub mod format;
+pub mod graph;
+pub mod health;
+pub mod log;
+pub mod peripheral;
+pub mod report;
+pub mod state;
+pub mod sysfs;
+pub mod uevent;
+pub mod watch;
+
+pub use battery::{Battery, Status};
+pub use configuration::{
+    calc_direction, calc_draw, calc_net_flow, calc_percentage, calc_percentage_on_basis, calc_status, calc_time, calc_time_on_basis,
+    calc_time_with_draw, get_configuration, get_configuration_with, CapacityBasis, Configuration,
+};
+pub use error::BatteryError;
+pub use estimate::{Estimator, History, Sample};
+pub use format::{
+    calc_display_time, format_battery_status, format_line, format_peripherals, format_short_status, format_status, BatteryOrder,
+    Colors, Format, Level, PerBattery, Ramps, Style, Thresholds,
+};
+pub use format::template::{Template, TemplateError};
+pub use health::BatteryHealth;
+pub use log::{LogEntry, LogFormat, Rotation};
+pub use peripheral::Peripheral;
+pub use state::{Reading, Record, State};
+pub use sysfs::{
+    get_ac_online, get_batteries, get_batteries_with, get_battery, get_battery_health, get_battery_healths, get_battery_healths_with,
+    get_battery_with, get_peripherals, Discovery, DEFAULT_THRESHOLD, PSEUDO_FS_PATH, SYSFS_ROOT_ENV,
+};
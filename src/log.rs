@@ -0,0 +1,257 @@
+//! Appending readings to a history log in CSV or JSON Lines, with size and age based rotation.
+//!
+//! CSV logs hold one row per battery and reading, sharing the timestamp and aggregated values of
+//! the reading, while JSON Lines logs hold one object per reading with its batteries nested.
+
+use crate::battery::Status;
+use crate::configuration::Configuration;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+const CSV_HEADER: &str = "timestamp,percentage,status,battery,energy_now,energy_full,power_now,battery_status,threshold";
+
+/// Encoding of a history log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Csv,
+    Jsonl,
+}
+
+impl LogFormat {
+    /// Guess the format of a log from its extension, defaulting to JSON Lines
+    pub fn from_path(path: &Path) -> LogFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => LogFormat::Csv,
+            _ => LogFormat::Jsonl,
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFormat, String> {
+        match s {
+            "csv" => Ok(LogFormat::Csv),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+/// A logged reading of a single battery
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogBattery {
+    pub name: String,
+    /// Unit: µWh
+    pub energy_now: u32,
+    /// Unit: µWh
+    pub energy_full: u32,
+    /// Unit: µW
+    pub power_now: u32,
+    pub status: Status,
+    /// Charge threshold percentage
+    pub threshold: f32,
+}
+
+/// A logged reading of all batteries
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Aggregated charge percentage
+    pub percentage: f32,
+    pub status: Status,
+    pub batteries: Vec<LogBattery>,
+}
+
+impl LogEntry {
+    /// Log given configuration at given time
+    pub fn new(config: &Configuration, timestamp: u64) -> LogEntry {
+        LogEntry {
+            timestamp,
+            percentage: config.percentage * 100f32,
+            status: config.status,
+            batteries: config
+                .batteries
+                .iter()
+                .map(|bat| LogBattery {
+                    name: bat.name.clone(),
+                    energy_now: bat.current_charge,
+                    energy_full: bat.max_charge,
+                    power_now: bat.power_draw,
+                    status: bat.status,
+                    threshold: bat.tlp_threshold * 100f32,
+                })
+                .collect(),
+        }
+    }
+
+    /// Total power draw across batteries in µW
+    pub fn power(&self) -> u32 {
+        self.batteries.iter().map(|bat| bat.power_now).sum()
+    }
+
+    /// Total current energy across batteries in µWh
+    pub fn energy(&self) -> u32 {
+        self.batteries.iter().map(|bat| bat.energy_now).sum()
+    }
+
+    /// Encode as lines in given format, without trailing newlines
+    fn encode(&self, format: LogFormat) -> Vec<String> {
+        match format {
+            LogFormat::Jsonl => vec![serde_json::to_string(self).unwrap()],
+            LogFormat::Csv => self
+                .batteries
+                .iter()
+                .map(|bat| {
+                    format!(
+                        "{},{:.2},{},{},{},{},{},{},{:.0}",
+                        self.timestamp, self.percentage, self.status, bat.name, bat.energy_now, bat.energy_full, bat.power_now, bat.status, bat.threshold
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// When to rotate a log, moving it to '<log>.1', and older logs one number up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    /// Size in bytes beyond which to rotate
+    pub max_size: Option<u64>,
+    /// Age of the first entry beyond which to rotate
+    pub max_age: Option<Duration>,
+    /// Number of rotated logs kept
+    pub keep: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation { max_size: Some(10 * 1024 * 1024), max_age: None, keep: 3 }
+    }
+}
+
+/// Append an entry to the log at given path, rotating it first if due
+pub fn append(path: &Path, format: LogFormat, entry: &LogEntry, rotation: &Rotation) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if rotation_due(path, format, entry.timestamp, rotation)? {
+        rotate(path, rotation.keep)?;
+    }
+    let mut lines = entry.encode(format);
+    let empty = fs::metadata(path).map(|metadata| metadata.len() == 0).unwrap_or(true);
+    if format == LogFormat::Csv && empty {
+        lines.insert(0, CSV_HEADER.to_string());
+    }
+    // A single write keeps concurrent appends from interleaving rows of one entry
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(format!("{}\n", lines.join("\n")).as_bytes())
+}
+
+fn rotation_due(path: &Path, format: LogFormat, now: u64, rotation: &Rotation) -> io::Result<bool> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if rotation.max_size.is_some_and(|max_size| metadata.len() >= max_size) {
+        return Ok(true);
+    }
+    if let Some(max_age) = rotation.max_age {
+        if let Some(first) = read_log(path, format)?.first() {
+            return Ok(now.saturating_sub(first.timestamp) >= max_age.as_secs());
+        }
+    }
+    Ok(false)
+}
+
+/// Path of the given rotated generation of a log, where generation zero is the log itself
+pub fn rotated_path(path: &Path, generation: usize) -> PathBuf {
+    if generation == 0 {
+        return path.to_path_buf();
+    }
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", generation));
+    path.with_file_name(name)
+}
+
+/// Rotate the log at given path, dropping generations beyond the number kept
+fn rotate(path: &Path, keep: usize) -> io::Result<()> {
+    if keep == 0 {
+        return fs::remove_file(path);
+    }
+    for generation in (0..keep).rev() {
+        let from = rotated_path(path, generation);
+        if from.exists() {
+            fs::rename(&from, rotated_path(path, generation + 1))?;
+        }
+    }
+    Ok(())
+}
+
+/// Read all entries of the log at given path, skipping malformed lines
+pub fn read_log(path: &Path, format: LogFormat) -> io::Result<Vec<LogEntry>> {
+    let reader = BufReader::new(fs::File::open(path)?);
+    let mut entries: Vec<LogEntry> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        match format {
+            LogFormat::Jsonl => entries.extend(serde_json::from_str(&line).ok()),
+            LogFormat::Csv => {
+                if let Some((entry, battery)) = parse_csv_row(&line) {
+                    // Rows of the same reading share their timestamp
+                    match entries.last_mut() {
+                        Some(last) if last.timestamp == entry.timestamp => last.batteries.push(battery),
+                        _ => entries.push(LogEntry { batteries: vec![battery], ..entry }),
+                    }
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Read all entries of the log at given path and its rotated generations, oldest first
+pub fn read_log_with_rotated(path: &Path, format: LogFormat) -> io::Result<Vec<LogEntry>> {
+    let mut generations = Vec::new();
+    let mut generation = 1;
+    while rotated_path(path, generation).exists() {
+        generations.push(rotated_path(path, generation));
+        generation += 1;
+    }
+    let mut entries = Vec::new();
+    for rotated in generations.iter().rev() {
+        entries.extend(read_log(rotated, format)?);
+    }
+    entries.extend(read_log(path, format)?);
+    Ok(entries)
+}
+
+/// Parse a CSV row into its entry, lacking batteries, and the battery of the row
+fn parse_csv_row(line: &str) -> Option<(LogEntry, LogBattery)> {
+    let fields: Vec<&str> = line.split(',').collect();
+    if fields.len() != 9 {
+        return None;
+    }
+    let entry = LogEntry {
+        timestamp: fields[0].parse().ok()?,
+        percentage: fields[1].parse().ok()?,
+        status: fields[2].parse().ok()?,
+        batteries: Vec::new(),
+    };
+    let battery = LogBattery {
+        name: fields[3].to_string(),
+        energy_now: fields[4].parse().ok()?,
+        energy_full: fields[5].parse().ok()?,
+        power_now: fields[6].parse().ok()?,
+        status: fields[7].parse().ok()?,
+        threshold: fields[8].parse().ok()?,
+    };
+    Some((entry, battery))
+}
@@ -1,212 +1,245 @@
-use std::fs;
+mod cli;
+
+use cli::{Options, Subcommand, USAGE};
+use poly_battery_status::estimate::unix_time;
+use poly_battery_status::uevent::UeventSocket;
+use poly_battery_status::{
+    get_battery_healths_with, get_configuration_with, get_peripherals, graph, health, log, peripheral, report, watch, BatteryError,
+    Configuration, Format, History, LogEntry, LogFormat, Record, Sample, State,
+};
 use std::env;
-use regex::Regex;
+use std::io::{self, Write};
+use std::process;
 use std::time::Duration;
-use std::str::FromStr;
-use std::env::Args;
 
-const PSEUDO_FS_PATH: &str = "/sys/class/power_supply/";
+/// Short line printed in place of the status when batteries could not be read
+const FALLBACK_STATUS: &str = "BAT ERR";
 
-/// Battery status enum. 'Passive' denotes the 'Unknown' state provided by sysfs
-/// when TLP enforces a threshold
-enum Status {
-    Charging,
-    Discharging,
-    Passive,
-}
-
-/// A configuration of batteries on a given machine
-struct Configuration {
-    time_to_completion: Duration,
-    percentage: f32,
-    status: Status,
-}
+fn main() {
+    // Consume first argument being current program
+    let options = Options::parse(env::args().skip(1).collect()).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    if options.help {
+        print!("{}", USAGE);
+        return;
+    }
+    match options.subcommand {
+        Subcommand::Status if options.peripherals => print_peripherals(&options),
+        Subcommand::Status => {}
+        Subcommand::Log => log_history(&options),
+        Subcommand::Report => report_history(&options),
+        Subcommand::Graph => graph_history(&options),
+        Subcommand::Health => print_health(&options),
+    }
+    if let Some(interval) = options.watch {
+        watch_status(&options, interval);
+    }
 
-/// A battery and all its concomitant data. Note that units are as-is, provided by sysfs in millis
-struct Battery {
-    status: Status,
-    // Unit: mWh
-    current_charge: u32,
-    // Unit: mWh
-    max_charge: u32,
-    // Unit: mW
-    power_draw: u32,
-    // Charge threshold percentage
-    tlp_threshold: f32,
-}
+    let mut config = match get_configuration_with(&options.root, &options.discovery) {
+        Ok(config) => config.with_basis(options.basis),
+        Err(BatteryError::NoBatteriesFound) => {
+            println!("{}", options.no_battery.text());
+            process::exit(options.no_battery_exit_code);
+        }
+        Err(e) => {
+            // Keep the status bar intact, reporting the error itself on stderr
+            println!("{}", FALLBACK_STATUS);
+            eprintln!("poly-battery-status: {}", e);
+            process::exit(e.exit_code());
+        }
+    };
 
-fn main() {
-    let config = get_configuration();
+    estimate(&mut config, &mut History::default(), &options);
 
     // Copy percentage for later use
-    let percentage = &config.percentage * 100 as f32;
-
-    print_status(config);
+    let percentage = config.percentage * 100f32;
 
-    let mut args = env::args();
-    // If arguments are supplied
-    if args.len() > 1 {
-        // Consume first argument being current program
-        args.next().unwrap();
+    println!("{}", options.format.render(&config, &options.style));
 
-        // Check that arguments exist after assumed threshold
-        if args.len() > 1 && percentage <= args.next().unwrap().parse().expect("Could not parse threshold") {
-            external_command(args);
+    // Check that a command exists after threshold
+    if let Some(threshold) = options.threshold {
+        if !options.command.is_empty() && percentage <= threshold {
+            external_command(&options.command);
         }
     }
 }
 
-// Execute external command from args supplied
-fn external_command(mut args: Args) {
-    use std::process::Command;
-    Command::new(args.next().unwrap())
-        .args(args)
-        .output()
-        .expect("Failed to execute external command");
-}
-
-/// Print a formatted status-line string
-fn print_status(config: Configuration) {
-    // Print percentage as an actual percentage and calculate pretty display-time
-    println!("{:.2}%{}", config.percentage * 100 as f32, calc_display_time(config.status, config.time_to_completion));
-}
-
-/// Calculate display-time and format display-string according to status
-fn calc_display_time(status: Status, time: Duration) -> String {
-    // Calculate hours and minutes for printing
-    let hours = time.as_secs() / 3600;
-    let minutes = time.as_secs() % 3600 / 60;
-    // Match on status and format string accordingly with {+, -}, printing empty when irrelevant
-    match status {
-        Status::Charging => {
-            format!(" (+{}:{:02})", hours, minutes)
-        }
-        Status::Discharging => {
-            format!(" (-{}:{:02})", hours, minutes)
+/// Continuously print the rendered configuration whenever it changes, never returning
+fn watch_status(options: &Options, interval: Duration) -> ! {
+    watch::install_refresh_signal();
+    // Without uevents, e.g. in a sandbox lacking netlink, fall back to polling alone
+    let events = if options.uevents { UeventSocket::open().ok() } else { None };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    // The i3bar protocol is a header followed by an endless array of status lines
+    let i3bar = options.format == Format::I3bar;
+    if i3bar {
+        emit(&mut out, "{\"version\":1}\n[");
+    }
+    let mut history = History::default();
+    let mut previous: Option<String> = None;
+    loop {
+        let line = match get_configuration_with(&options.root, &options.discovery) {
+            Ok(config) => {
+                let mut config = config.with_basis(options.basis);
+                estimate(&mut config, &mut history, options);
+                options.format.render(&config, &options.style)
+            }
+            Err(BatteryError::NoBatteriesFound) => options.no_battery.text().to_string(),
+            Err(e) => {
+                eprintln!("poly-battery-status: {}", e);
+                FALLBACK_STATUS.to_string()
+            }
+        };
+        if previous.as_ref() != Some(&line) {
+            if i3bar {
+                emit(&mut out, &format!("[{}],", line));
+            } else {
+                emit(&mut out, &line);
+            }
+            previous = Some(line);
         }
-        Status::Passive => { "".to_string() }
+        watch::wait_for_events(events.as_ref(), interval);
     }
 }
 
-/// Find, calculate, and return a configuration of batteries and its values
-fn get_configuration() -> Configuration {
-    // Matches any number of batteries on sysfs
-    let regex = Regex::new(r"^BAT\d+$").unwrap();
-
-    // Temporary vector for holding discovered batteries
-    let mut batteries: Vec<Battery> = Vec::new();
-
-    // Read 'power_supply' dir on sysfs
-    let paths = fs::read_dir(PSEUDO_FS_PATH).unwrap();
-
-    // For each result, match on batteries, and dispatch getters
-    // for Battery-struct creation before pushing onto vector
-    for path in paths {
-        if let Ok(e) = path {
-            if regex.is_match(e.file_name().to_str().unwrap()) {
-                let battery_name: String = e.file_name().to_str().unwrap().parse().unwrap();
-                batteries.push(Battery {
-                    current_charge: get_current_charge(&battery_name),
-                    max_charge: get_max_charge(&battery_name),
-                    status: get_status(&battery_name),
-                    power_draw: get_power_draw(&battery_name),
-                    tlp_threshold: get_tlp_threshold(&battery_name),
-                });
-            }
-        }
+/// Append readings to the history log, once or every interval when watching
+fn log_history(options: &Options) -> ! {
+    let path = options.log_file.as_ref().expect("log file is set by option parsing");
+    let format = options.log_format.unwrap_or_else(|| LogFormat::from_path(path));
+    if options.watch.is_some() {
+        watch::install_refresh_signal();
     }
-    // Find status of all batteries.
-    // Assumes that all batteries will be either charging or discharging, if not passive
-    let mut stat = Status::Passive;
-    for bat in &batteries {
-        match bat.status {
-            Status::Charging => {
-                stat = Status::Charging;
-                break;
+    let events = match options.watch {
+        Some(_) if options.uevents => UeventSocket::open().ok(),
+        _ => None,
+    };
+    loop {
+        // The same discovery as the status line, so logged values match what the bar shows
+        let code = match get_configuration_with(&options.root, &options.discovery) {
+            Ok(config) => {
+                let entry = LogEntry::new(&config, unix_time() as u64);
+                match log::append(path, format, &entry, &options.rotation) {
+                    Ok(()) => 0,
+                    Err(e) => {
+                        eprintln!("poly-battery-status: could not log to {}: {}", path.display(), e);
+                        1
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("poly-battery-status: {}", e);
+                e.exit_code()
             }
-            Status::Discharging => {
-                stat = Status::Discharging;
-                break;
+        };
+        match options.watch {
+            Some(interval) => {
+                watch::wait_for_events(events.as_ref(), interval);
             }
-            _ => {}
+            None => process::exit(code),
         }
     }
+}
 
-    // Create configuration, calculating both time-to-completion and percentage.
-    let configuration = Configuration {
-        time_to_completion: calc_time(&batteries, &stat),
-        percentage: calc_percentage(&batteries),
-        status: stat,
+/// Print per-day statistics of the history log and its rotated logs
+fn report_history(options: &Options) -> ! {
+    let entries = read_history(options);
+    let summaries = report::summarize(&entries);
+    if options.json {
+        println!("{}", report::format_json(&summaries));
+    } else {
+        print!("{}", report::format_table(&summaries));
+    }
+    process::exit(0);
+}
+
+/// Chart the recent history log and its rotated logs, sized to the terminal
+fn graph_history(options: &Options) -> ! {
+    let entries = read_history(options);
+    let end = unix_time() as u64;
+    let start = end.saturating_sub((options.hours * 3600f32) as u64);
+    let size = graph::Size {
+        width: options.width.or_else(graph::terminal_width).unwrap_or(80),
+        height: options.height,
     };
-    return configuration;
+    print!("{}", graph::render(&entries, start, end, size));
+    process::exit(0);
 }
 
-/// Calculate time-to-completion based on current values
-fn calc_time(bats: &Vec<Battery>, stat: &Status) -> Duration {
-    let total_current_charge: u32 = bats.iter().map(|x| x.current_charge).sum();
-    let total_max_charge: u32 = bats.iter().map(|x| x.max_charge).sum();
-    let total_draw: u32 = bats.iter().map(|x| x.power_draw).sum();
-    // Average of charge thresholds, if asymmetrical multi-battery setup
-    let tlp_threshold: f32 = bats.iter().map(|x| x.tlp_threshold).sum::<f32>() / bats.len() as f32;
-    match stat {
-        Status::Passive => {
-            Duration::new(0, 0)
-        }
-        Status::Discharging => {
-            Duration::new((((total_current_charge as f32) / (total_draw as f32)) * 3600f32) as u64, 0)
+/// Print wear, cycle count and identification of all batteries
+fn print_health(options: &Options) -> ! {
+    let batteries = get_battery_healths_with(&options.root, &options.discovery).and_then(|batteries| {
+        if batteries.is_empty() {
+            Err(BatteryError::NoBatteriesFound)
+        } else {
+            Ok(batteries)
         }
-        Status::Charging => {
-            Duration::new(((
-                (total_max_charge as f32 * tlp_threshold) - total_current_charge as f32)
-                / (total_draw as f32) * 3600f32) as u64, 0)
+    });
+    match batteries {
+        Ok(batteries) if options.json => println!("{}", health::format_json(&batteries)),
+        Ok(batteries) => print!("{}", health::format_table(&batteries)),
+        Err(e) => {
+            eprintln!("poly-battery-status: {}", e);
+            process::exit(e.exit_code());
         }
     }
+    process::exit(0);
 }
 
-/// Calculate charge-percentage across all batteries
-fn calc_percentage(bats: &Vec<Battery>) -> f32 {
-    let total_charge: u32 = bats.iter().map(|x| x.max_charge).sum();
-    let total_current_charge: u32 = bats.iter().map(|x| x.current_charge).sum();
-
-    return (total_current_charge as f32) / (total_charge as f32);
-}
-
-/// Return current charge of given battery
-fn get_current_charge(bat: &String) -> u32 {
-    let cap = fs::read_to_string(format!("{}{}/energy_now", PSEUDO_FS_PATH, bat)).unwrap();
-    return u32::from_str(cap.trim()).unwrap();
+/// Print charge and status of all peripherals, printing nothing if none are connected
+fn print_peripherals(options: &Options) -> ! {
+    match get_peripherals(&options.root) {
+        Ok(peripherals) if options.json => println!("{}", peripheral::format_json(&peripherals)),
+        Ok(peripherals) => print!("{}", peripheral::format_table(&peripherals, &options.style.thresholds)),
+        Err(e) => {
+            eprintln!("poly-battery-status: {}", e);
+            process::exit(e.exit_code());
+        }
+    }
+    process::exit(0);
 }
 
-/// Return max charge of given battery
-fn get_max_charge(bat: &String) -> u32 {
-    let cap = fs::read_to_string(format!("{}{}/energy_full", PSEUDO_FS_PATH, bat)).unwrap();
-    return u32::from_str(cap.trim()).unwrap();
+/// Read the history log and its rotated logs, exiting if unreadable
+fn read_history(options: &Options) -> Vec<LogEntry> {
+    let path = options.log_file.as_ref().expect("log file is set by option parsing");
+    let format = options.log_format.unwrap_or_else(|| LogFormat::from_path(path));
+    log::read_log_with_rotated(path, format).unwrap_or_else(|e| {
+        eprintln!("poly-battery-status: could not read log {}: {}", path.display(), e);
+        process::exit(1);
+    })
 }
 
-/// Return current power draw of given battery
-fn get_power_draw(bat: &String) -> u32 {
-    let power_draw = fs::read_to_string(format!("{}{}/power_now", PSEUDO_FS_PATH, bat)).unwrap();
-    return u32::from_str(power_draw.trim()).unwrap();
+/// Record given configuration, replacing its time-to-completion by an estimate from recent samples
+/// kept in given history, or in the state file when enabled
+fn estimate(config: &mut Configuration, history: &mut History, options: &Options) {
+    let timestamp = unix_time();
+    let window = options.estimator.window();
+    let state = options.state_file.as_ref().map(|path| {
+        State::update(path, Record::new(config, timestamp), options.state_max_age.max(window)).map_err(|e| {
+            // Failing to keep state only loses smoothing across invocations
+            eprintln!("poly-battery-status: could not update state in {}: {}", path.display(), e);
+        })
+    });
+    match state {
+        Some(Ok(state)) => *history = state.history(),
+        _ => history.push(Sample::new(config, timestamp), window),
+    }
+    config.time_to_completion = options.estimator.estimate(config, history);
 }
 
-fn get_tlp_threshold(bat: &String) -> f32 {
-    let tlp_threshold = fs::read_to_string(format!("{}{}/charge_stop_threshold", PSEUDO_FS_PATH, bat)).unwrap();
-    return f32::from_str(tlp_threshold.trim()).unwrap() / 100f32;
+/// Print and flush a line, exiting once the reading end, e.g. the status bar, has gone away
+fn emit(out: &mut impl Write, line: &str) {
+    if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+        process::exit(0);
+    }
 }
 
-/// Return current status of given battery
-fn get_status(bat: &String) -> Status {
-    let raw_status = fs::read_to_string(format!("{}{}/status", PSEUDO_FS_PATH, bat)).unwrap();
-    let stat = raw_status.trim();
-    match stat {
-        "Unknown" => { Status::Passive }
-        "Not charging" => { Status::Passive }
-        "Full" => { Status::Passive }
-        "Charging" => { Status::Charging }
-        "Discharging" => { Status::Discharging }
-        _ => {
-            panic!("Could not match status of battery: {}, status received was: {}", bat, stat);
-        }
+// Execute external command from args supplied
+fn external_command(args: &[String]) {
+    use std::process::Command;
+    if let Err(e) = Command::new(&args[0]).args(&args[1..]).output() {
+        eprintln!("Failed to execute external command {}: {}", args[0], e);
     }
 }
-
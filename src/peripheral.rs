@@ -0,0 +1,82 @@
+//! Batteries of peripherals, such as mice, keyboards and headsets
+
+use crate::battery::Status;
+use crate::format::{Level, Thresholds};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// A battery powering a peripheral rather than the system, i.e. of 'Device' scope
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Peripheral {
+    /// Name of the power supply on sysfs, e.g. 'hidpp_battery_0'
+    pub name: String,
+    pub model_name: Option<String>,
+    /// Charge percentage, if reported
+    pub capacity: Option<f32>,
+    /// Coarse charge, e.g. 'Low' or 'High', reported by devices lacking a percentage
+    pub capacity_level: Option<String>,
+    pub status: Option<Status>,
+}
+
+impl Peripheral {
+    /// Name to show, preferring the model over the supply name
+    pub fn label(&self) -> &str {
+        self.model_name.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Charge as a fraction, estimated from the capacity level where no percentage is reported
+    pub fn fraction(&self) -> Option<f32> {
+        match (self.capacity, self.capacity_level.as_deref()) {
+            (Some(capacity), _) => Some(capacity / 100f32),
+            (None, Some("Critical")) => Some(0.05),
+            (None, Some("Low")) => Some(0.2),
+            (None, Some("Normal")) => Some(0.5),
+            (None, Some("High")) => Some(0.8),
+            (None, Some("Full")) => Some(1f32),
+            _ => None,
+        }
+    }
+
+    /// Severity of this peripheral's charge by given thresholds, or by its capacity level if it
+    /// reports no percentage. A charging peripheral is never considered low
+    pub fn level(&self, thresholds: &Thresholds) -> Level {
+        if self.status == Some(Status::Charging) {
+            return Level::Normal;
+        }
+        match (self.capacity, self.capacity_level.as_deref()) {
+            (Some(capacity), _) if capacity <= thresholds.critical => Level::Critical,
+            (Some(capacity), _) if capacity <= thresholds.warning => Level::Warning,
+            (None, Some("Critical")) => Level::Critical,
+            (None, Some("Low")) => Level::Warning,
+            _ => Level::Normal,
+        }
+    }
+
+    /// Charge as text, e.g. '85%' or 'low', or '?' if unknown
+    pub fn charge(&self) -> String {
+        match (self.capacity, &self.capacity_level) {
+            (Some(capacity), _) => format!("{:.0}%", capacity),
+            (None, Some(level)) => level.to_lowercase(),
+            (None, None) => "?".to_string(),
+        }
+    }
+}
+
+/// Format peripherals as a table with one row per peripheral
+pub fn format_table(peripherals: &[Peripheral], thresholds: &Thresholds) -> String {
+    let mut table = String::new();
+    for peripheral in peripherals {
+        let status = peripheral.status.map_or_else(|| "-".to_string(), |status| status.to_string());
+        let warning = match peripheral.level(thresholds) {
+            Level::Normal => String::new(),
+            level => format!("  {}", level),
+        };
+        writeln!(table, "{}  {}  {}  {}{}", peripheral.name, peripheral.label(), peripheral.charge(), status, warning).unwrap();
+    }
+    table
+}
+
+/// Format peripherals as a JSON array
+pub fn format_json(peripherals: &[Peripheral]) -> String {
+    serde_json::to_string_pretty(peripherals).unwrap()
+}
@@ -0,0 +1,163 @@
+//! Per-day discharge statistics over a history log
+
+use crate::battery::Status;
+use crate::log::LogEntry;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// Largest gap between consecutive readings still considered continuous, in seconds. Longer gaps,
+/// e.g. while suspended or not logging, are left out of durations and rates
+pub const MAX_GAP: u64 = 30 * 60;
+
+/// Statistics of a single day, in UTC
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DaySummary {
+    /// Date as 'YYYY-MM-DD'
+    pub date: String,
+    pub readings: usize,
+    /// Average rate of energy loss while discharging, in W
+    pub average_discharge_w: Option<f32>,
+    /// Longest continuous time discharging, in seconds
+    pub longest_on_battery: u64,
+    /// Number of times charging started
+    pub charge_cycles: u32,
+    /// Lowest aggregated percentage
+    pub deepest_discharge: f32,
+    /// Time spent at or above the charge threshold, in seconds
+    pub above_threshold: u64,
+}
+
+/// Percentage points below the charge threshold still considered at it, as batteries stopping at
+/// their threshold tend to settle slightly below it
+const THRESHOLD_TOLERANCE: f32 = 1f32;
+
+/// Summarize given entries per day, sorting them chronologically first as logs written by several
+/// loggers or across a clock step back may be out of order
+pub fn summarize(entries: &[LogEntry]) -> Vec<DaySummary> {
+    let mut entries = entries.to_vec();
+    entries.sort_by_key(|entry| entry.timestamp);
+    let mut summaries = Vec::new();
+    let mut start = 0;
+    while start < entries.len() {
+        let day = entries[start].timestamp / 86_400;
+        let end = start + entries[start..].iter().take_while(|entry| entry.timestamp / 86_400 == day).count();
+        // Include the readings around the day, so pairs crossing midnight count towards both days
+        let context = &entries[start.saturating_sub(1)..(end + 1).min(entries.len())];
+        summaries.push(summarize_day(day, context));
+        start = end;
+    }
+    summaries
+}
+
+/// Summarize a day from its entries, in chronological order, and any entries adjacent to it
+fn summarize_day(day: u64, entries: &[LogEntry]) -> DaySummary {
+    let (day_start, day_end) = (day * 86_400, (day + 1) * 86_400);
+    let in_day = |entry: &LogEntry| entry.timestamp / 86_400 == day;
+    let mut discharged_energy = 0f64;
+    let mut discharging_time = 0u64;
+    let mut longest_on_battery = 0u64;
+    let mut run = 0u64;
+    let mut charge_cycles = 0;
+    let mut above_threshold = 0u64;
+
+    for pair in entries.windows(2) {
+        let (previous, next) = (&pair[0], &pair[1]);
+        let gap = next.timestamp.saturating_sub(previous.timestamp);
+        if in_day(next) && next.status == Status::Charging && previous.status != Status::Charging {
+            charge_cycles += 1;
+        }
+        if gap > MAX_GAP {
+            run = 0;
+            continue;
+        }
+        // Part of the pair's interval falling within this day
+        let overlap = next.timestamp.min(day_end).saturating_sub(previous.timestamp.max(day_start));
+        let share = if gap > 0 { overlap as f64 / gap as f64 } else if in_day(previous) { 1f64 } else { 0f64 };
+        if previous.status == Status::Discharging && next.status == Status::Discharging {
+            discharged_energy += (previous.energy() as f64 - next.energy() as f64) * share;
+            discharging_time += overlap;
+            run += overlap;
+            longest_on_battery = longest_on_battery.max(run);
+        } else {
+            run = 0;
+        }
+        if previous.percentage + THRESHOLD_TOLERANCE >= threshold(previous) {
+            above_threshold += overlap;
+        }
+    }
+
+    let day_entries = || entries.iter().filter(|entry| in_day(entry));
+
+    DaySummary {
+        date: format_date(day),
+        readings: day_entries().count(),
+        // Energy in µWh over seconds, converted to W
+        average_discharge_w: if discharging_time > 0 {
+            Some((discharged_energy / discharging_time as f64 * 3600f64 / 1_000_000f64) as f32)
+        } else {
+            None
+        },
+        longest_on_battery,
+        charge_cycles,
+        deepest_discharge: day_entries().map(|entry| entry.percentage).fold(f32::INFINITY, f32::min),
+        above_threshold,
+    }
+}
+
+/// Charge threshold percentage of an entry, weighted by the capacity of its batteries
+fn threshold(entry: &LogEntry) -> f32 {
+    let capacity: f32 = entry.batteries.iter().map(|bat| bat.energy_full as f32).sum();
+    if capacity == 0f32 {
+        return 100f32;
+    }
+    entry.batteries.iter().map(|bat| bat.energy_full as f32 * bat.threshold).sum::<f32>() / capacity
+}
+
+/// Format days since the Unix epoch as 'YYYY-MM-DD' in the proleptic Gregorian calendar
+pub fn format_date(days: u64) -> String {
+    // Shift the epoch to 0000-03-01, so leap days fall at the end of each 400-year era
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Format a duration of seconds as 'h:mm'
+fn format_duration(secs: u64) -> String {
+    format!("{}:{:02}", secs / 3600, secs % 3600 / 60)
+}
+
+/// Format summaries as a plain table
+pub fn format_table(summaries: &[DaySummary]) -> String {
+    let mut table = String::from("date        readings  avg discharge  longest on battery  charge cycles  deepest  above threshold\n");
+    for summary in summaries {
+        let average = match summary.average_discharge_w {
+            Some(watts) => format!("{:.2} W", watts),
+            None => "-".to_string(),
+        };
+        writeln!(
+            table,
+            "{}  {:>8}  {:>13}  {:>18}  {:>13}  {:>6.2}%  {:>15}",
+            summary.date,
+            summary.readings,
+            average,
+            format_duration(summary.longest_on_battery),
+            summary.charge_cycles,
+            summary.deepest_discharge,
+            format_duration(summary.above_threshold)
+        )
+        .unwrap();
+    }
+    table
+}
+
+/// Format summaries as a JSON array
+pub fn format_json(summaries: &[DaySummary]) -> String {
+    serde_json::to_string_pretty(summaries).unwrap()
+}
@@ -0,0 +1,165 @@
+//! Persistent state of recent readings, letting one-shot invocations smooth estimates.
+//!
+//! The state file lives under `$XDG_STATE_HOME/poly-battery-status/` by default and holds
+//! timestamped per-battery readings, pruned by age. Updates take an exclusive lock on a sibling
+//! lock file and replace the state file atomically, so concurrent invocations never corrupt it.
+
+use crate::battery::Status;
+use crate::configuration::Configuration;
+use crate::estimate::{History, Sample};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Most records kept in a state file, regardless of their age
+const MAX_RECORDS: usize = 4096;
+
+/// A reading of a single battery
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    pub name: String,
+    /// Unit: µWh
+    pub energy: u32,
+    /// Unit: µW
+    pub power: u32,
+    pub status: Status,
+}
+
+/// Readings of all batteries at a point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub timestamp: f64,
+    pub batteries: Vec<Reading>,
+}
+
+impl Record {
+    /// Record the batteries of given configuration at given time
+    pub fn new(config: &Configuration, timestamp: f64) -> Record {
+        Record {
+            timestamp,
+            batteries: config
+                .batteries
+                .iter()
+                .map(|bat| Reading { name: bat.name.clone(), energy: bat.current_charge, power: bat.power_draw, status: bat.status })
+                .collect(),
+        }
+    }
+
+    /// Aggregate this record into a sample of totals, with status found as for a configuration
+    pub fn sample(&self) -> Sample {
+        let status = Status::aggregate(self.batteries.iter().map(|reading| reading.status));
+        let power = match status {
+            Status::Mixed => self.batteries.iter().map(|reading| reading.status.flow(reading.power)).sum::<i64>().unsigned_abs() as u32,
+            _ => self.batteries.iter().map(|reading| reading.power).sum(),
+        };
+        Sample {
+            timestamp: self.timestamp,
+            energy: self.batteries.iter().map(|reading| reading.energy).sum(),
+            power,
+            status,
+        }
+    }
+}
+
+/// Recent records in chronological order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub records: Vec<Record>,
+}
+
+impl State {
+    /// Directory of this tool's state, under '$XDG_STATE_HOME' or '~/.local/state'
+    pub fn directory() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+        };
+        Some(base.join("poly-battery-status"))
+    }
+
+    /// Default location of the state file
+    pub fn default_path() -> Option<PathBuf> {
+        Some(State::directory()?.join("state.json"))
+    }
+
+    /// Read the state file at given path, being empty if it does not exist or is unreadable
+    pub fn load(path: &Path) -> State {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    /// Add a record, dropping records older than given age relative to it
+    pub fn push(&mut self, record: Record, max_age: Duration) {
+        let oldest = record.timestamp - max_age.as_secs_f64();
+        self.records.push(record);
+        self.records.retain(|r| r.timestamp >= oldest);
+        if self.records.len() > MAX_RECORDS {
+            self.records.drain(..self.records.len() - MAX_RECORDS);
+        }
+    }
+
+    /// Atomically add a record to the state file at given path, returning the updated state.
+    /// Concurrent updates are serialized through an exclusive lock
+    pub fn update(path: &Path, record: Record, max_age: Duration) -> io::Result<State> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let _lock = Lock::acquire(&sibling(path, "lock"))?;
+        let mut state = State::load(path);
+        state.push(record, max_age);
+
+        // Write to a temporary file first, so readers never observe a partial state
+        let temporary = sibling(path, &format!("{}.tmp", std::process::id()));
+        fs::write(&temporary, serde_json::to_string(&state)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(state)
+    }
+
+    /// Aggregate records into a history of samples for estimators
+    pub fn history(&self) -> History {
+        History { samples: self.records.iter().map(Record::sample).collect() }
+    }
+}
+
+/// A path next to given one, with given suffix appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// An exclusive advisory lock on a file, released when dropped
+struct Lock {
+    file: File,
+}
+
+impl Lock {
+    fn acquire(path: &Path) -> io::Result<Lock> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                return Ok(Lock { file });
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::flock(self.file.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
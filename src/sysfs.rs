@@ -0,0 +1,413 @@
+//! Discovery of batteries and reading of their attributes on sysfs
+
+use crate::battery::{Battery, Status};
+use crate::error::BatteryError;
+use crate::health::BatteryHealth;
+use crate::peripheral::Peripheral;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Default location of power supplies on sysfs
+pub const PSEUDO_FS_PATH: &str = "/sys/class/power_supply/";
+
+/// Environment variable overriding the sysfs root, e.g. for reading a captured snapshot
+pub const SYSFS_ROOT_ENV: &str = "POLY_BATTERY_STATUS_SYSFS_ROOT";
+
+/// Charge stop threshold assumed for batteries not reporting one, as a fraction
+pub const DEFAULT_THRESHOLD: f32 = 0.8;
+
+/// Settings for discovering and reading batteries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discovery {
+    /// Charge stop threshold as a fraction, assumed for batteries not reporting one
+    pub default_threshold: f32,
+    /// Name patterns of batteries to consider, or all if empty. In patterns, '*' matches any text
+    /// and '?' any single character
+    pub include: Vec<String>,
+    /// Name patterns of batteries to ignore, even if included
+    pub exclude: Vec<String>,
+    /// Whether to also read batteries of peripherals, kept apart from system batteries
+    pub peripherals: bool,
+}
+
+impl Default for Discovery {
+    fn default() -> Discovery {
+        Discovery { default_threshold: DEFAULT_THRESHOLD, include: Vec::new(), exclude: Vec::new(), peripherals: false }
+    }
+}
+
+impl Discovery {
+    /// Whether a battery of given name is to be considered
+    pub fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|pattern| glob_match(pattern, name)))
+            && !self.exclude.iter().any(|pattern| glob_match(pattern, name))
+    }
+}
+
+/// Whether given name matches a pattern, where '*' matches any text and '?' any single character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last '*' and the name position it was last tried to match up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last '*' swallow one more character
+                Some((after, from)) => {
+                    backtrack = Some((after, from + 1));
+                    p = after;
+                    n = from + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Attribute family exposed by a battery on sysfs
+enum Unit {
+    /// 'energy_now', 'energy_full' and 'power_now' in µWh and µW
+    Energy,
+    /// 'charge_now', 'charge_full' and 'current_now' in µAh and µA, converted using voltage
+    Charge,
+}
+
+/// Find all batteries under given sysfs root and read their values
+pub fn get_batteries(root: &Path) -> Result<Vec<Battery>, BatteryError> {
+    get_batteries_with(root, &Discovery::default())
+}
+
+/// Find all batteries under given sysfs root and read their values using given settings
+pub fn get_batteries_with(root: &Path, discovery: &Discovery) -> Result<Vec<Battery>, BatteryError> {
+    read_batteries(root, discovery, get_ac_online(root))
+}
+
+/// Read all batteries under given sysfs root, given whether external power is connected
+pub(crate) fn read_batteries(root: &Path, discovery: &Discovery, ac_online: Option<bool>) -> Result<Vec<Battery>, BatteryError> {
+    get_battery_names(root, discovery)?.iter().map(|name| read_battery(root, name, discovery, ac_online)).collect()
+}
+
+/// Whether any external power supply, i.e. of type 'Mains' or 'USB' such as 'AC', 'ADP1' or
+/// 'ucsi-source-psy-*', is online under given sysfs root, or none if there is no such supply.
+/// Supplies that cannot be read, e.g. USB-C ports on flaky firmware, are skipped, so adapters never
+/// keep batteries from being read
+pub fn get_ac_online(root: &Path) -> Option<bool> {
+    let mut online = None;
+    for name in get_supply_names(root).ok()? {
+        let kind: Option<String> = get_optional_attribute(root, &name, "type").ok().flatten();
+        if !kind.is_some_and(|kind| kind == "Mains" || kind.starts_with("USB")) {
+            continue;
+        }
+        // USB-C ports report 1 or 2 when supplying power, depending on the negotiated mode
+        if let Some(state) = get_optional_attribute::<u32>(root, &name, "online").ok().flatten() {
+            online = Some(online.unwrap_or(false) || state > 0);
+        }
+    }
+    online
+}
+
+/// Find all batteries under given sysfs root and read their capacity and identification
+pub fn get_battery_healths(root: &Path) -> Result<Vec<BatteryHealth>, BatteryError> {
+    get_battery_healths_with(root, &Discovery::default())
+}
+
+/// Find all batteries under given sysfs root using given settings and read their capacity and
+/// identification
+pub fn get_battery_healths_with(root: &Path, discovery: &Discovery) -> Result<Vec<BatteryHealth>, BatteryError> {
+    get_battery_names(root, discovery)?.iter().map(|name| get_battery_health(root, name)).collect()
+}
+
+/// Return names of all power supplies under given sysfs root, in a stable order
+fn get_supply_names(root: &Path) -> Result<Vec<String>, BatteryError> {
+    // Read 'power_supply' dir on sysfs
+    let paths = fs::read_dir(root).map_err(|e| BatteryError::from_io(root, e))?;
+
+    let mut names: Vec<String> = paths
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().map(String::from))
+        // Supplies are directories, or rather symlinks to them
+        .filter(|name| root.join(name).is_dir())
+        .collect();
+    // Directory order is arbitrary, keep supplies in a stable order
+    names.sort();
+    Ok(names)
+}
+
+/// Return names of all system batteries under given sysfs root matching given settings, in a
+/// stable order. Batteries of peripherals, such as mice, have a 'Device' scope and are left out
+fn get_battery_names(root: &Path, discovery: &Discovery) -> Result<Vec<String>, BatteryError> {
+    Ok(get_supply_names(root)?.into_iter().filter(|name| is_battery(root, name, false) && discovery.matches(name)).collect())
+}
+
+/// Whether given power supply is a battery of a peripheral, i.e. of 'Device' scope, or of the
+/// system otherwise. Supplies whose type or scope cannot be read are neither, so a flaky adapter
+/// or peripheral never keeps the system batteries from being read
+fn is_battery(root: &Path, name: &str, peripheral: bool) -> bool {
+    if get_optional_attribute::<String>(root, name, "type").ok().flatten().as_deref() != Some("Battery") {
+        return false;
+    }
+    // Most system batteries report no scope at all
+    match get_optional_attribute::<String>(root, name, "scope") {
+        Ok(scope) => (scope.as_deref() == Some("Device")) == peripheral,
+        Err(_) => false,
+    }
+}
+
+/// Find all batteries of peripherals under given sysfs root and read what they report. As
+/// peripherals come and go, e.g. over Bluetooth, their attributes are read leniently
+pub fn get_peripherals(root: &Path) -> Result<Vec<Peripheral>, BatteryError> {
+    let mut peripherals = Vec::new();
+    for name in get_supply_names(root)? {
+        if !is_battery(root, &name, true) {
+            continue;
+        }
+        let status = get_optional_attribute::<String>(root, &name, "status").ok().flatten().map(|status| match status.as_str() {
+            "Charging" => Status::Charging,
+            "Discharging" => Status::Discharging,
+            _ => Status::Passive,
+        });
+        peripherals.push(Peripheral {
+            model_name: get_optional_attribute(root, &name, "model_name").ok().flatten(),
+            capacity: get_optional_attribute(root, &name, "capacity").ok().flatten(),
+            capacity_level: get_optional_attribute(root, &name, "capacity_level").ok().flatten(),
+            status,
+            name,
+        });
+    }
+    Ok(peripherals)
+}
+
+/// Read all values of a single, named battery under given sysfs root
+pub fn get_battery(root: &Path, bat: &str) -> Result<Battery, BatteryError> {
+    get_battery_with(root, bat, &Discovery::default())
+}
+
+/// Read all values of a single, named battery under given sysfs root using given settings
+pub fn get_battery_with(root: &Path, bat: &str, discovery: &Discovery) -> Result<Battery, BatteryError> {
+    read_battery(root, bat, discovery, get_ac_online(root))
+}
+
+/// Read all values of a single, named battery, given whether external power is connected
+fn read_battery(root: &Path, bat: &str, discovery: &Discovery, ac_online: Option<bool>) -> Result<Battery, BatteryError> {
+    let unit = get_unit(root, bat);
+    let current_charge = get_current_charge(root, bat, &unit)?;
+    let max_charge = get_max_charge(root, bat, &unit)?;
+    let power_draw = get_power_draw(root, bat, &unit)?;
+    let mut battery = Battery {
+        name: bat.to_string(),
+        current_charge,
+        max_charge,
+        design_charge: get_design_charge(root, bat, &unit)?,
+        status: get_status(root, bat, ac_online, power_draw)?,
+        power_draw,
+        tlp_threshold: get_tlp_threshold(root, bat)?.unwrap_or(discovery.default_threshold),
+        start_threshold: get_start_threshold(root, bat)?,
+    };
+    if battery.status == Status::Passive && is_waiting(&battery) {
+        battery.status = Status::Waiting;
+    }
+    Ok(battery)
+}
+
+/// Read capacity and identification of a single, named battery under given sysfs root
+pub fn get_battery_health(root: &Path, bat: &str) -> Result<BatteryHealth, BatteryError> {
+    let unit = get_unit(root, bat);
+    Ok(BatteryHealth {
+        name: bat.to_string(),
+        energy_full: get_max_charge(root, bat, &unit)?,
+        energy_full_design: get_design_charge(root, bat, &unit)?,
+        cycle_count: get_optional_attribute(root, bat, "cycle_count")?,
+        manufacturer: get_optional_attribute(root, bat, "manufacturer")?,
+        model_name: get_optional_attribute(root, bat, "model_name")?,
+        technology: get_optional_attribute(root, bat, "technology")?,
+        serial_number: get_optional_attribute(root, bat, "serial_number")?,
+    })
+}
+
+/// Return the attribute family provided by given battery
+fn get_unit(root: &Path, bat: &str) -> Unit {
+    if root.join(bat).join("energy_now").exists() {
+        Unit::Energy
+    } else {
+        Unit::Charge
+    }
+}
+
+/// Return the raw, trimmed contents of an attribute of given battery
+fn get_raw_attribute(root: &Path, bat: &str, attribute: &str) -> Result<String, BatteryError> {
+    let path = root.join(bat).join(attribute);
+    match fs::read_to_string(&path) {
+        Ok(raw) => Ok(raw.trim().to_string()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(BatteryError::MissingAttribute {
+            battery: bat.to_string(),
+            attribute: attribute.to_string(),
+        }),
+        Err(e) => Err(BatteryError::from_io(&path, e)),
+    }
+}
+
+/// Return an attribute of given battery parsed into the requested type
+fn get_parsed_attribute<T: FromStr>(root: &Path, bat: &str, attribute: &str) -> Result<T, BatteryError> {
+    let raw = get_raw_attribute(root, bat, attribute)?;
+    T::from_str(&raw).map_err(|_| BatteryError::UnparsableValue {
+        battery: bat.to_string(),
+        attribute: attribute.to_string(),
+        value: raw,
+    })
+}
+
+/// Return an attribute of given battery parsed into the requested type, or none if not provided
+fn get_optional_attribute<T: FromStr>(root: &Path, bat: &str, attribute: &str) -> Result<Option<T>, BatteryError> {
+    match get_parsed_attribute(root, bat, attribute) {
+        Ok(value) => Ok(Some(value)),
+        Err(BatteryError::MissingAttribute { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Return a raw, signed integer attribute of given battery
+fn get_attribute(root: &Path, bat: &str, attribute: &str) -> Result<i64, BatteryError> {
+    get_parsed_attribute(root, bat, attribute)
+}
+
+/// Return voltage used for converting charge to energy, preferring the design minimum
+fn get_design_voltage(root: &Path, bat: &str) -> Result<u64, BatteryError> {
+    if root.join(bat).join("voltage_min_design").exists() {
+        get_parsed_attribute(root, bat, "voltage_min_design")
+    } else {
+        get_parsed_attribute(root, bat, "voltage_now")
+    }
+}
+
+/// Convert a µAh or µA attribute of given battery to µWh or µW respectively, given voltage in µV,
+/// rejecting values not fitting into the result
+fn charge_to_energy(bat: &str, attribute: &str, charge: u64, voltage: u64) -> Result<u32, BatteryError> {
+    charge
+        .checked_mul(voltage)
+        .and_then(|energy| u32::try_from(energy / 1_000_000).ok())
+        .ok_or_else(|| out_of_range(bat, attribute, charge))
+}
+
+/// Check that an energy attribute of given battery fits into µWh, rejecting negative values
+fn to_energy(bat: &str, attribute: &str, energy: i64) -> Result<u32, BatteryError> {
+    u32::try_from(energy).map_err(|_| out_of_range(bat, attribute, energy))
+}
+
+/// Error for an attribute of given battery whose value is out of the supported range
+fn out_of_range(bat: &str, attribute: &str, value: impl ToString) -> BatteryError {
+    BatteryError::UnparsableValue { battery: bat.to_string(), attribute: attribute.to_string(), value: value.to_string() }
+}
+
+/// Return current charge of given battery in µWh
+fn get_current_charge(root: &Path, bat: &str, unit: &Unit) -> Result<u32, BatteryError> {
+    Ok(match unit {
+        Unit::Energy => to_energy(bat, "energy_now", get_attribute(root, bat, "energy_now")?)?,
+        Unit::Charge => {
+            let charge = get_parsed_attribute(root, bat, "charge_now")?;
+            charge_to_energy(bat, "charge_now", charge, get_design_voltage(root, bat)?)?
+        }
+    })
+}
+
+/// Return max charge of given battery in µWh
+fn get_max_charge(root: &Path, bat: &str, unit: &Unit) -> Result<u32, BatteryError> {
+    Ok(match unit {
+        Unit::Energy => to_energy(bat, "energy_full", get_attribute(root, bat, "energy_full")?)?,
+        Unit::Charge => {
+            let charge = get_parsed_attribute(root, bat, "charge_full")?;
+            charge_to_energy(bat, "charge_full", charge, get_design_voltage(root, bat)?)?
+        }
+    })
+}
+
+/// Return design capacity of given battery in µWh, or none if not provided
+fn get_design_charge(root: &Path, bat: &str, unit: &Unit) -> Result<Option<u32>, BatteryError> {
+    Ok(match unit {
+        Unit::Energy => match get_optional_attribute(root, bat, "energy_full_design")? {
+            Some(energy) => Some(to_energy(bat, "energy_full_design", energy)?),
+            None => None,
+        },
+        Unit::Charge => match get_optional_attribute(root, bat, "charge_full_design")? {
+            Some(charge) => Some(charge_to_energy(bat, "charge_full_design", charge, get_design_voltage(root, bat)?)?),
+            None => None,
+        },
+    })
+}
+
+/// Return current power draw of given battery in µW. Some drivers report a signed power or current,
+/// negative when discharging
+fn get_power_draw(root: &Path, bat: &str, unit: &Unit) -> Result<u32, BatteryError> {
+    Ok(match unit {
+        Unit::Energy => {
+            let power = get_attribute(root, bat, "power_now")?;
+            u32::try_from(power.unsigned_abs()).map_err(|_| out_of_range(bat, "power_now", power))?
+        }
+        Unit::Charge => {
+            let current = get_attribute(root, bat, "current_now")?;
+            charge_to_energy(bat, "current_now", current.unsigned_abs(), get_parsed_attribute(root, bat, "voltage_now")?)
+                .map_err(|_| out_of_range(bat, "current_now", current))?
+        }
+    })
+}
+
+/// Return charge stop threshold of given battery as a fraction, preferring the generic attribute
+/// of recent kernels over the older, vendor-specific one, or none if neither is provided
+fn get_tlp_threshold(root: &Path, bat: &str) -> Result<Option<f32>, BatteryError> {
+    let threshold = match get_optional_attribute::<f32>(root, bat, "charge_control_end_threshold")? {
+        Some(threshold) => Some(threshold),
+        None => get_optional_attribute(root, bat, "charge_stop_threshold")?,
+    };
+    Ok(threshold.map(|threshold| threshold / 100f32))
+}
+
+/// Return charge start threshold of given battery as a fraction, or none if not provided
+fn get_start_threshold(root: &Path, bat: &str) -> Result<Option<f32>, BatteryError> {
+    let threshold = match get_optional_attribute::<f32>(root, bat, "charge_control_start_threshold")? {
+        Some(threshold) => Some(threshold),
+        None => get_optional_attribute(root, bat, "charge_start_threshold")?,
+    };
+    Ok(threshold.map(|threshold| threshold / 100f32))
+}
+
+/// Whether given passive battery sits between its start and stop thresholds, where it will not
+/// charge until dropping below the start threshold. Within a percentage point of the stop
+/// threshold, the battery is considered to have stopped there instead
+fn is_waiting(battery: &Battery) -> bool {
+    let percentage = battery.percentage();
+    match battery.start_threshold {
+        Some(start) => percentage >= start && percentage + 0.01 < battery.tlp_threshold,
+        None => false,
+    }
+}
+
+/// Return current status of given battery, given whether external power is connected and its
+/// power draw in µW. Without external power, a battery reporting 'Unknown' or 'Not charging' while
+/// drawing power is discharging, whereas with it, the battery idles, e.g. at its threshold
+fn get_status(root: &Path, bat: &str, ac_online: Option<bool>, power_draw: u32) -> Result<Status, BatteryError> {
+    let stat = get_raw_attribute(root, bat, "status")?;
+    match stat.as_str() {
+        "Unknown" | "Not charging" if ac_online == Some(false) && power_draw > 0 => { Ok(Status::Discharging) }
+        "Unknown" => { Ok(Status::Passive) }
+        "Not charging" => { Ok(Status::Passive) }
+        "Full" => { Ok(Status::Passive) }
+        "Charging" => { Ok(Status::Charging) }
+        "Discharging" => { Ok(Status::Discharging) }
+        _ => {
+            Err(BatteryError::UnknownStatus { battery: bat.to_string(), status: stat })
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Kernel uevents of power supplies, received over a netlink socket
+
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+
+/// Netlink multicast group of uevents as broadcast by the kernel
+const KERNEL_GROUP: u32 = 1;
+
+/// Largest uevent message read at once
+const BUFFER_SIZE: usize = 8192;
+
+/// A kernel uevent, e.g. 'change@/devices/.../power_supply/AC' with its properties
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uevent {
+    pub action: String,
+    pub devpath: String,
+    /// 'KEY=VALUE' properties in the order sent
+    pub properties: Vec<(String, String)>,
+}
+
+impl Uevent {
+    /// Parse a raw kernel uevent message of NUL-separated fields. Messages re-broadcast by udev,
+    /// which carry a binary header, are not kernel uevents and give none
+    pub fn parse(message: &[u8]) -> Option<Uevent> {
+        let message = String::from_utf8_lossy(message);
+        let mut fields = message.split('\0').filter(|field| !field.is_empty());
+        let (action, devpath) = fields.next()?.split_once('@')?;
+        let properties = fields
+            .filter_map(|field| field.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Some(Uevent { action: action.to_string(), devpath: devpath.to_string(), properties })
+    }
+
+    /// Value of given property, if present
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Whether this event concerns a power supply, e.g. an AC plug or a battery status change
+    pub fn is_power_supply(&self) -> bool {
+        self.property("SUBSYSTEM") == Some("power_supply")
+    }
+}
+
+/// A socket receiving kernel uevents
+#[derive(Debug)]
+pub struct UeventSocket {
+    fd: OwnedFd,
+}
+
+impl UeventSocket {
+    /// Subscribe to kernel uevents over a netlink socket
+    pub fn open() -> io::Result<UeventSocket> {
+        unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+                libc::NETLINK_KOBJECT_UEVENT,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = OwnedFd::from_raw_fd(fd);
+            let mut address: libc::sockaddr_nl = std::mem::zeroed();
+            address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            address.nl_groups = KERNEL_GROUP;
+            let bound = libc::bind(
+                fd.as_raw_fd(),
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            );
+            if bound < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(UeventSocket { fd })
+        }
+    }
+
+    /// Receive all pending uevents without blocking
+    pub fn receive(&self) -> io::Result<Vec<Uevent>> {
+        let mut events = Vec::new();
+        let mut buffer = [0u8; BUFFER_SIZE];
+        loop {
+            let received = unsafe {
+                libc::recv(self.fd.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), libc::MSG_DONTWAIT)
+            };
+            if received < 0 {
+                let error = io::Error::last_os_error();
+                return match error.kind() {
+                    io::ErrorKind::WouldBlock => Ok(events),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => Err(error),
+                };
+            }
+            if received == 0 {
+                return Ok(events);
+            }
+            events.extend(Uevent::parse(&buffer[..received as usize]));
+        }
+    }
+}
+
+/// Receive uevents from any datagram socket, e.g. one end of a local pair feeding synthetic events
+impl From<UnixDatagram> for UeventSocket {
+    fn from(socket: UnixDatagram) -> UeventSocket {
+        UeventSocket { fd: OwnedFd::from(socket) }
+    }
+}
+
+impl AsRawFd for UeventSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
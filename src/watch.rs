@@ -0,0 +1,97 @@
+//! Waiting between refreshes of a continuously watched configuration
+
+use crate::uevent::UeventSocket;
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Set by SIGUSR1 to request an immediate refresh
+static REFRESH: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_refresh(_: libc::c_int) {
+    REFRESH.store(true, Ordering::SeqCst);
+}
+
+/// Install a SIGUSR1 handler which interrupts [`wait`] to force an immediate refresh.
+///
+/// SIGUSR1 is blocked on the calling thread outside of [`wait`], which unblocks it atomically
+/// while sleeping, so a signal arriving between refreshes is never lost.
+pub fn install_refresh_signal() {
+    let handler: extern "C" fn(libc::c_int) = request_refresh;
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut());
+
+        let mut blocked: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut blocked);
+        libc::sigaddset(&mut blocked, libc::SIGUSR1);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &blocked, std::ptr::null_mut());
+    }
+}
+
+/// Take a pending refresh request, if any
+pub fn refresh_requested() -> bool {
+    REFRESH.swap(false, Ordering::SeqCst)
+}
+
+/// Reason for ending a wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wakeup {
+    /// The interval passed
+    Interval,
+    /// A refresh was requested through SIGUSR1
+    Signal,
+    /// A power supply changed, e.g. AC was plugged or a battery changed status
+    PowerSupply,
+}
+
+/// Sleep for given interval, returning early when a refresh is requested through SIGUSR1
+pub fn wait(interval: Duration) -> Wakeup {
+    wait_for_events(None, interval)
+}
+
+/// Sleep for given interval, returning early when a refresh is requested through SIGUSR1 or a
+/// power supply uevent arrives on given socket. Uevents of other subsystems are skipped
+pub fn wait_for_events(events: Option<&UeventSocket>, interval: Duration) -> Wakeup {
+    let deadline = Instant::now() + interval;
+    let mut unblocked: libc::sigset_t = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::pthread_sigmask(libc::SIG_SETMASK, std::ptr::null(), &mut unblocked);
+        libc::sigdelset(&mut unblocked, libc::SIGUSR1);
+    }
+    // A negative descriptor is ignored by poll, leaving a plain sleep
+    let mut pollfd = libc::pollfd {
+        fd: events.map_or(-1, |socket| socket.as_raw_fd()),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    loop {
+        if refresh_requested() {
+            return Wakeup::Signal;
+        }
+        let remaining = match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if remaining > Duration::new(0, 0) => remaining,
+            _ => return Wakeup::Interval,
+        };
+        let timeout = libc::timespec {
+            tv_sec: remaining.as_secs() as libc::time_t,
+            tv_nsec: remaining.subsec_nanos() as libc::c_long,
+        };
+        // Interrupted polls loop around to check for a refresh request
+        let ready = unsafe { libc::ppoll(&mut pollfd, 1, &timeout, &unblocked) };
+        if pollfd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+            pollfd.fd = -1;
+            continue;
+        }
+        if let (true, Some(socket)) = (ready > 0 && pollfd.revents != 0, events) {
+            match socket.receive() {
+                Ok(received) if received.iter().any(|event| event.is_power_supply()) => return Wakeup::PowerSupply,
+                Ok(_) => {}
+                // A broken socket falls back to polling for the remaining interval
+                Err(_) => pollfd.fd = -1,
+            }
+        }
+    }
+}
@@ -0,0 +1,376 @@
+mod common;
+
+use common::FakeSysfs;
+use poly_battery_status::{
+    calc_display_time, calc_percentage_on_basis, calc_status, calc_time, calc_time_on_basis, format_status, get_ac_online, get_batteries,
+    get_batteries_with, get_battery, get_battery_with, get_configuration, get_configuration_with, get_peripherals, Battery, BatteryError, CapacityBasis,
+    Configuration, Discovery, Level, Ramps, Status, Style, Thresholds, DEFAULT_THRESHOLD,
+};
+use std::time::Duration;
+
+fn battery(name: &str, status: Status, current_charge: u32, max_charge: u32, power_draw: u32) -> Battery {
+    Battery { name: name.to_string(), status, current_charge, max_charge, design_charge: None, power_draw, tlp_threshold: 0.8, start_threshold: None }
+}
+
+#[test]
+fn batteries_are_discovered_in_order() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true)
+        .energy_battery("BAT1", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .energy_battery("BAT0", "Charging", 20_000_000, 50_000_000, 10_000_000, 80);
+    let batteries = get_batteries(sysfs.root()).unwrap();
+    let names: Vec<&str> = batteries.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, ["BAT0", "BAT1"]);
+    assert_eq!(batteries[0].status, Status::Charging);
+    assert_eq!(batteries[1].current_charge, 40_000_000);
+}
+
+#[test]
+fn configuration_from_sysfs() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    let config = get_configuration(sysfs.root()).unwrap();
+    assert_eq!(config.status, Status::Discharging);
+    assert_eq!(config.time_to_completion, Duration::from_secs(9000));
+    assert_eq!(config.batteries.len(), 1);
+}
+
+#[test]
+fn configuration_from_batteries() {
+    let config = Configuration::from_batteries(vec![
+        battery("BAT0", Status::Passive, 40_000_000, 50_000_000, 0),
+        battery("BAT1", Status::Discharging, 10_000_000, 50_000_000, 20_000_000),
+    ]);
+    assert_eq!(config.status, Status::Discharging);
+    assert_eq!(format_status(&config), "50.00% (-2:30)");
+}
+
+#[test]
+fn display_time_by_status() {
+    let time = Duration::from_secs(3 * 3600 + 5 * 60);
+    assert_eq!(calc_display_time(Status::Charging, time), " (+3:05)");
+    assert_eq!(calc_display_time(Status::Discharging, time), " (-3:05)");
+    assert_eq!(calc_display_time(Status::Passive, time), "");
+}
+
+#[test]
+fn missing_attribute_is_reported() {
+    let sysfs = FakeSysfs::new();
+    sysfs.supply("BAT0", &[("type", "Battery"), ("status", "Discharging"), ("energy_now", "25000000")]);
+    match get_configuration(sysfs.root()) {
+        Err(BatteryError::MissingAttribute { battery, attribute }) => {
+            assert_eq!(battery, "BAT0");
+            assert_eq!(attribute, "energy_full");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn unparsable_value_is_reported() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80)
+        .supply("BAT0", &[("power_now", "lots")]);
+    match get_configuration(sysfs.root()) {
+        Err(BatteryError::UnparsableValue { attribute, value, .. }) => {
+            assert_eq!(attribute, "power_now");
+            assert_eq!(value, "lots");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn negative_energy_is_reported() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80)
+        .supply("BAT0", &[("energy_now", "-5")]);
+    match get_configuration(sysfs.root()) {
+        Err(BatteryError::UnparsableValue { attribute, value, .. }) => {
+            assert_eq!(attribute, "energy_now");
+            assert_eq!(value, "-5");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+    sysfs.supply("BAT0", &[("energy_now", "25000000"), ("energy_full_design", "5000000000")]);
+    assert!(matches!(get_battery(sysfs.root(), "BAT0"), Err(BatteryError::UnparsableValue { .. })));
+}
+
+#[test]
+fn out_of_range_charge_is_reported() {
+    let sysfs = FakeSysfs::new();
+    sysfs.charge_battery("BAT0", "Discharging", 3_000_000, 4_000_000, 1_000_000, 12_000_000);
+    for (attribute, value) in [("charge_now", "3000000000"), ("charge_now", "9000000000000000000"), ("charge_full", "-9223372036854775808")] {
+        sysfs.supply("BAT0", &[("charge_now", "3000000"), ("charge_full", "4000000"), (attribute, value)]);
+        match get_battery(sysfs.root(), "BAT0") {
+            Err(BatteryError::UnparsableValue { attribute: reported, .. }) => assert_eq!(reported, attribute),
+            other => panic!("unexpected result for {}={}: {:?}", attribute, value, other),
+        }
+    }
+    sysfs.supply("BAT0", &[("charge_full", "4000000"), ("current_now", "-9223372036854775808")]);
+    assert!(matches!(get_battery(sysfs.root(), "BAT0"), Err(BatteryError::UnparsableValue { .. })));
+    // A signed current is taken by its magnitude
+    sysfs.supply("BAT0", &[("current_now", "-1000000")]);
+    assert_eq!(get_battery(sysfs.root(), "BAT0").unwrap().power_draw, 12_000_000);
+
+    sysfs.energy_battery("BAT1", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80).supply("BAT1", &[("power_now", "-5000000000")]);
+    assert!(matches!(get_battery(sysfs.root(), "BAT1"), Err(BatteryError::UnparsableValue { .. })));
+}
+
+#[test]
+fn unknown_status_is_reported() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Exploding", 25_000_000, 50_000_000, 10_000_000, 80);
+    assert!(matches!(get_configuration(sysfs.root()), Err(BatteryError::UnknownStatus { .. })));
+}
+
+#[test]
+fn no_batteries_is_reported() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true);
+    assert!(matches!(get_configuration(sysfs.root()), Err(BatteryError::NoBatteriesFound)));
+}
+
+#[test]
+fn empty_configuration_has_no_nan() {
+    let config = Configuration::from_batteries(Vec::new());
+    assert_eq!(config.percentage, 0f32);
+    assert_eq!(config.time_to_completion, Duration::new(0, 0));
+}
+
+#[test]
+fn discharging_without_draw_has_no_time() {
+    let config = Configuration::from_batteries(vec![battery("BAT0", Status::Discharging, 10_000_000, 50_000_000, 0)]);
+    assert_eq!(config.time_to_completion, Duration::new(0, 0));
+}
+
+#[test]
+fn level_by_thresholds() {
+    let thresholds = Thresholds::default();
+    let low = Configuration::from_batteries(vec![battery("BAT0", Status::Discharging, 12_000_000, 50_000_000, 1_000_000)]);
+    let critical = Configuration::from_batteries(vec![battery("BAT0", Status::Passive, 5_000_000, 50_000_000, 0)]);
+    let charging = Configuration::from_batteries(vec![battery("BAT0", Status::Charging, 5_000_000, 50_000_000, 1_000_000)]);
+    assert_eq!(thresholds.level(&low), Level::Warning);
+    assert_eq!(thresholds.level(&critical), Level::Critical);
+    assert_eq!(thresholds.level(&charging), Level::Normal);
+    assert!(!Style::default().urgent(&critical));
+}
+
+#[test]
+fn ramp_icons_spread_over_charge() {
+    let ramps = Ramps { charging: vec!["C".to_string()], discharging: vec!["0".to_string(), "1".to_string(), "2".to_string()], passive: Vec::new() };
+    let icon = |status, current_charge| {
+        let config = Configuration::from_batteries(vec![battery("BAT0", status, current_charge, 30_000_000, 1_000_000)]);
+        ramps.icon(&config).to_string()
+    };
+    assert_eq!(icon(Status::Discharging, 0), "0");
+    assert_eq!(icon(Status::Discharging, 15_000_000), "1");
+    assert_eq!(icon(Status::Discharging, 30_000_000), "2");
+    assert_eq!(icon(Status::Charging, 15_000_000), "C");
+    assert_eq!(icon(Status::Passive, 15_000_000), "");
+}
+
+#[test]
+fn time_to_full_on_design_basis() {
+    let mut bat = battery("BAT0", Status::Charging, 20_000_000, 40_000_000, 10_000_000);
+    bat.design_charge = Some(30_000_000);
+    // 80% of design capacity, reachable by the worn battery
+    assert_eq!(calc_time_on_basis(&[bat.clone()], &Status::Charging, 10_000_000, CapacityBasis::Design), Duration::from_secs(1440));
+    assert_eq!(calc_percentage_on_basis(&[bat.clone()], CapacityBasis::Design), 20_000_000f32 / 30_000_000f32);
+    // Without a reported design capacity, the max charge is used instead
+    bat.design_charge = None;
+    assert_eq!(calc_percentage_on_basis(&[bat], CapacityBasis::Design), 0.5);
+}
+
+#[test]
+fn thresholds_prefer_generic_attributes() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Charging", 20_000_000, 50_000_000, 10_000_000, 80)
+        .supply("BAT0", &[("charge_control_end_threshold", "90"), ("charge_control_start_threshold", "75")]);
+    let bat = get_battery(sysfs.root(), "BAT0").unwrap();
+    assert_eq!(bat.tlp_threshold, 0.9);
+    assert_eq!(bat.start_threshold, Some(0.75));
+}
+
+#[test]
+fn missing_threshold_falls_back_to_default() {
+    let sysfs = FakeSysfs::new();
+    sysfs.supply("BAT0", &[("type", "Battery"), ("status", "Charging"), ("energy_now", "20000000"), ("energy_full", "50000000"), ("power_now", "10000000")]);
+    assert_eq!(get_battery(sysfs.root(), "BAT0").unwrap().tlp_threshold, DEFAULT_THRESHOLD);
+    let discovery = Discovery { default_threshold: 1f32, ..Discovery::default() };
+    assert_eq!(get_battery_with(sysfs.root(), "BAT0", &discovery).unwrap().tlp_threshold, 1f32);
+    // Charging 30 Wh to full at 10 W
+    let config = get_configuration_with(sysfs.root(), &discovery).unwrap();
+    assert_eq!(config.time_to_completion, Duration::from_secs(3 * 3600));
+}
+
+#[test]
+fn passive_between_thresholds_is_waiting() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Not charging", 35_000_000, 50_000_000, 0, 80)
+        .supply("BAT0", &[("charge_control_start_threshold", "60")])
+        .energy_battery("BAT1", "Not charging", 39_800_000, 50_000_000, 0, 80)
+        .supply("BAT1", &[("charge_control_start_threshold", "60")])
+        .energy_battery("BAT2", "Not charging", 20_000_000, 50_000_000, 0, 80);
+    let batteries = get_batteries(sysfs.root()).unwrap();
+    assert_eq!(batteries[0].status, Status::Waiting);
+    // Stopped at the threshold rather than waiting below it
+    assert_eq!(batteries[1].status, Status::Passive);
+    // Without a start threshold, there is nothing to wait for
+    assert_eq!(batteries[2].status, Status::Passive);
+    assert_eq!(calc_status(&batteries), Status::Waiting);
+    assert_eq!(calc_status(&batteries[1..]), Status::Passive);
+}
+
+#[test]
+fn time_to_full_sums_per_battery_targets() {
+    let mut bat0 = battery("BAT0", Status::Charging, 10_000_000, 20_000_000, 0);
+    bat0.tlp_threshold = 0.8;
+    let mut bat1 = battery("BAT1", Status::Charging, 40_000_000, 80_000_000, 0);
+    bat1.tlp_threshold = 1.0;
+    // 16 + 80 = 96 Wh target, where averaging thresholds would give 90 Wh
+    let bats = [bat0, bat1];
+    assert_eq!(calc_time_on_basis(&bats, &Status::Charging, 46_000_000, CapacityBasis::Full), Duration::from_secs(3600));
+    assert_eq!(calc_percentage_on_basis(&bats, CapacityBasis::Threshold), 50f32 / 96f32);
+}
+
+#[test]
+fn threshold_basis_is_full_at_threshold() {
+    let bat = battery("BAT0", Status::Passive, 45_000_000, 50_000_000, 0);
+    // Charged beyond the threshold of 80% before it was set
+    assert_eq!(bat.percentage_of(CapacityBasis::Threshold), 1f32);
+    assert_eq!(bat.charge_target(CapacityBasis::Full), 40_000_000);
+}
+
+#[test]
+fn mixed_status_from_opposing_batteries() {
+    let charging = battery("BAT0", Status::Charging, 20_000_000, 50_000_000, 5_000_000);
+    let discharging = battery("BAT1", Status::Discharging, 30_000_000, 50_000_000, 15_000_000);
+    let passive = battery("BAT2", Status::Passive, 30_000_000, 50_000_000, 0);
+    let bats = [charging, discharging, passive];
+    assert_eq!(calc_status(&bats), Status::Mixed);
+    assert_eq!(calc_status(&bats[1..]), Status::Discharging);
+    // Net 10 W out of the batteries, holding 80 Wh
+    assert_eq!(calc_time(&bats, &Status::Mixed), Duration::from_secs(8 * 3600));
+    let config = Configuration::from_batteries(bats.to_vec());
+    assert_eq!(config.net_flow(), -10_000_000);
+    assert_eq!(config.direction(), Status::Discharging);
+    assert_eq!(format_status(&config), "53.33% (-8:00) +BAT0 -BAT1");
+}
+
+#[test]
+fn detects_adapters_by_type() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80);
+    assert_eq!(get_ac_online(sysfs.root()), None);
+    sysfs.supply("ADP1", &[("type", "Mains"), ("online", "0")]);
+    assert_eq!(get_ac_online(sysfs.root()), Some(false));
+    // USB-C source ports report 2 when supplying power in some modes
+    sysfs.supply("ucsi-source-psy-USBC000:001", &[("type", "USB"), ("online", "2")]);
+    assert_eq!(get_ac_online(sysfs.root()), Some(true));
+    assert_eq!(get_configuration(sysfs.root()).unwrap().ac_online, Some(true));
+}
+
+#[test]
+fn unreadable_adapters_are_skipped() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80).supply("AC", &[("type", "Mains"), ("online", "x")]);
+    assert_eq!(get_ac_online(sysfs.root()), None);
+    assert_eq!(get_batteries(sysfs.root()).unwrap().len(), 1);
+    assert_eq!(get_battery(sysfs.root(), "BAT0").unwrap().status, Status::Discharging);
+    assert_eq!(get_configuration(sysfs.root()).unwrap().ac_online, None);
+    sysfs.supply("ucsi-source-psy-USBC000:001", &[("type", "USB"), ("online", "1")]);
+    assert_eq!(get_ac_online(sysfs.root()), Some(true));
+}
+
+#[test]
+fn unreadable_peripherals_are_skipped() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80)
+        .supply("hidpp_battery_0", &[("type", "Battery"), ("capacity", "85")])
+        .supply("AC", &[("online", "1")]);
+    // Reading a directory in place of an attribute fails, like a supply vanishing mid-read
+    std::fs::create_dir_all(sysfs.root().join("hidpp_battery_0/scope")).unwrap();
+    std::fs::create_dir_all(sysfs.root().join("AC/type")).unwrap();
+    assert_eq!(get_batteries(sysfs.root()).unwrap().len(), 1);
+    assert_eq!(get_configuration(sysfs.root()).unwrap().batteries.len(), 1);
+    assert!(get_peripherals(sysfs.root()).unwrap().is_empty());
+}
+
+#[test]
+fn adapter_disambiguates_idle_statuses() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 25_000_000, 50_000_000, 5_000_000, 80)
+        .energy_battery("BAT1", "Not charging", 40_000_000, 50_000_000, 0, 80)
+        .supply("ACAD", &[("type", "Mains"), ("online", "0")]);
+    let batteries = get_batteries(sysfs.root()).unwrap();
+    // Drawing power without external power means discharging
+    assert_eq!(batteries[0].status, Status::Discharging);
+    // Idle, e.g. while the other battery supplies the system
+    assert_eq!(batteries[1].status, Status::Passive);
+
+    sysfs.ac(true);
+    sysfs.supply("ACAD", &[("online", "1")]);
+    assert_eq!(get_batteries(sysfs.root()).unwrap()[0].status, Status::Passive);
+}
+
+#[test]
+fn discovers_batteries_by_type_and_scope() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("CMB0", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80)
+        .energy_battery("macsmc-battery", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80)
+        .energy_battery("hidpp_battery_0", "Discharging", 1_000_000, 2_000_000, 0, 100)
+        .supply("hidpp_battery_0", &[("scope", "Device")])
+        .supply("AC", &[("type", "Mains"), ("online", "0")]);
+    let names: Vec<String> = get_batteries(sysfs.root()).unwrap().into_iter().map(|bat| bat.name).collect();
+    assert_eq!(names, ["CMB0", "macsmc-battery"]);
+}
+
+#[test]
+fn discovery_patterns_filter_by_name() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80)
+        .energy_battery("BAT1", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80)
+        .energy_battery("BATT", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80);
+    let names = |discovery: Discovery| -> Vec<String> {
+        get_batteries_with(sysfs.root(), &discovery).unwrap().into_iter().map(|bat| bat.name).collect()
+    };
+    let include = |patterns: &[&str]| Discovery { include: patterns.iter().map(|p| p.to_string()).collect(), ..Discovery::default() };
+    assert_eq!(names(include(&["BAT?"])), ["BAT0", "BAT1", "BATT"]);
+    assert_eq!(names(include(&["*1", "*T"])), ["BAT1", "BATT"]);
+    assert_eq!(names(include(&["B*T*"])), ["BAT0", "BAT1", "BATT"]);
+    assert_eq!(names(include(&["BAT"])), Vec::<String>::new());
+    let discovery = Discovery { exclude: vec!["*0".to_string()], ..include(&["BAT*"]) };
+    assert_eq!(names(discovery), ["BAT1", "BATT"]);
+}
+
+#[test]
+fn peripherals_are_kept_apart() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 5_000_000, 80)
+        .supply("hidpp_battery_0", &[("type", "Battery"), ("scope", "Device"), ("model_name", "MX Master 3"), ("capacity", "85"), ("status", "Discharging")])
+        .supply("hid-headset-battery", &[("type", "Battery"), ("scope", "Device"), ("capacity_level", "Low")]);
+    let peripherals = get_peripherals(sysfs.root()).unwrap();
+    assert_eq!(peripherals.len(), 2);
+    assert_eq!(peripherals[0].label(), "hid-headset-battery");
+    assert_eq!(peripherals[0].charge(), "low");
+    assert_eq!(peripherals[0].fraction(), Some(0.2));
+    assert_eq!(peripherals[0].level(&Thresholds::default()), Level::Warning);
+    assert_eq!(peripherals[1].label(), "MX Master 3");
+    assert_eq!(peripherals[1].status, Some(Status::Discharging));
+    assert_eq!(peripherals[1].level(&Thresholds::default()), Level::Normal);
+
+    // Only read alongside the system batteries when asked to, and never aggregated
+    assert!(get_configuration(sysfs.root()).unwrap().peripherals.is_empty());
+    let config = get_configuration_with(sysfs.root(), &Discovery { peripherals: true, ..Discovery::default() }).unwrap();
+    assert_eq!(config.batteries.len(), 1);
+    assert_eq!(config.peripherals, peripherals);
+    assert_eq!(config.percentage, 0.5);
+}
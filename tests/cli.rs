@@ -0,0 +1,392 @@
+mod common;
+
+use common::FakeSysfs;
+use std::process::Command;
+
+#[test]
+fn discharging_single_battery() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(false).energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(sysfs.stdout(&[]), "50.00% (-2:30)");
+}
+
+#[test]
+fn charging_single_battery_respects_threshold() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true).energy_battery("BAT0", "Charging", 20_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(sysfs.stdout(&[]), "40.00% (+2:00)");
+}
+
+#[test]
+fn full_battery_omits_time() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true).energy_battery("BAT0", "Full", 50_000_000, 50_000_000, 0, 100);
+    assert_eq!(sysfs.stdout(&[]), "100.00%");
+}
+
+#[test]
+fn tlp_threshold_reached_is_passive() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true).energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80);
+    assert_eq!(sysfs.stdout(&[]), "80.00%");
+}
+
+#[test]
+fn two_batteries_discharging() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(false)
+        .energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .energy_battery("BAT1", "Discharging", 10_000_000, 50_000_000, 20_000_000, 80);
+    assert_eq!(sysfs.stdout(&[]), "50.00% (-2:30)");
+}
+
+#[test]
+fn charge_based_battery_is_converted_to_energy() {
+    let sysfs = FakeSysfs::new();
+    // 2.5 Ah at 10 V is 25 Wh, drawing 1 A at 10 V is 10 W
+    sysfs.ac(false).charge_battery("BAT0", "Discharging", 2_500_000, 5_000_000, 1_000_000, 10_000_000);
+    assert_eq!(sysfs.stdout(&[]), "50.00% (-2:30)");
+}
+
+#[test]
+fn mixed_charge_and_energy_batteries_aggregate() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(false)
+        .charge_battery("BAT0", "Discharging", 2_500_000, 5_000_000, 1_000_000, 10_000_000)
+        .energy_battery("BAT1", "Discharging", 5_000_000, 50_000_000, 5_000_000, 80);
+    assert_eq!(sysfs.stdout(&[]), "30.00% (-2:00)");
+}
+
+#[test]
+fn sysfs_root_from_environment() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    let output = Command::new(env!("CARGO_BIN_EXE_poly-battery-status"))
+        .env("POLY_BATTERY_STATUS_SYSFS_ROOT", sysfs.root())
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim_end(), "50.00% (-2:30)");
+}
+
+#[test]
+fn external_command_runs_below_threshold() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 5_000_000, 50_000_000, 10_000_000, 80);
+    let marker = sysfs.root().join("marker");
+    sysfs.run(&["15", "touch", marker.to_str().unwrap()]);
+    assert!(marker.exists());
+}
+
+#[test]
+fn external_command_skipped_above_threshold() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    let marker = sysfs.root().join("marker");
+    sysfs.run(&["15", "touch", marker.to_str().unwrap()]);
+    assert!(!marker.exists());
+}
+
+#[test]
+fn unknown_status_prints_fallback_line() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Exploding", 25_000_000, 50_000_000, 10_000_000, 80);
+    let output = sysfs.run(&[]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim_end(), "BAT ERR");
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Exploding"));
+}
+
+#[test]
+fn missing_attribute_exit_code() {
+    let sysfs = FakeSysfs::new();
+    sysfs.supply("BAT0", &[("type", "Battery"), ("status", "Discharging")]);
+    assert_eq!(sysfs.run(&[]).status.code(), Some(3));
+}
+
+#[test]
+fn no_battery_defaults_to_empty_output() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true);
+    let output = sysfs.run(&[]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\n");
+    assert_eq!(output.status.code(), Some(6));
+}
+
+#[test]
+fn no_battery_ac_with_custom_exit_code() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true);
+    let output = sysfs.run(&["--no-battery", "ac", "--no-battery-exit-code", "0"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim_end(), "AC");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn no_battery_custom_text() {
+    let sysfs = FakeSysfs::new();
+    assert_eq!(sysfs.stdout(&["--no-battery=desktop"]), "desktop");
+}
+
+#[test]
+fn unknown_option_is_rejected() {
+    let sysfs = FakeSysfs::new();
+    assert_eq!(sysfs.run(&["--frobnicate"]).status.code(), Some(2));
+}
+
+#[test]
+fn zero_intervals_are_rejected() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80);
+    for interval in ["0", "0.0001s", "0.4ms", "-5s", "nan"] {
+        let output = sysfs.run(&["--watch", interval]);
+        assert_eq!(output.status.code(), Some(2), "{}", interval);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("Invalid interval"), "{}", interval);
+    }
+    assert_eq!(sysfs.run(&["--estimator", "ema", "--estimator-window", "0.5ms"]).status.code(), Some(2));
+    assert_eq!(sysfs.run(&["--state-max-age", "0.0001s"]).status.code(), Some(2));
+    assert_eq!(sysfs.stdout(&["--estimator", "ema", "--estimator-window", "1ms"]), "80.00%");
+}
+
+#[test]
+fn i3blocks_json_critical_is_urgent() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 5_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(
+        sysfs.stdout(&["--format", "i3blocks-json"]),
+        r##"{"full_text":"10.00% (-0:30)","short_text":"10%","color":"#FF0000","urgent":true}"##
+    );
+}
+
+#[test]
+fn i3blocks_json_normal_has_no_color() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(
+        sysfs.stdout(&["--format", "i3blocks-json"]),
+        r#"{"full_text":"50.00% (-2:30)","short_text":"50%","urgent":false}"#
+    );
+}
+
+#[test]
+fn i3bar_with_custom_thresholds_and_colors() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(
+        sysfs.stdout(&["--format=i3bar", "--warning", "60", "--color-warning", "#FFAE00"]),
+        r##"{"name":"battery","full_text":"50.00% (-2:30)","short_text":"50%","color":"#FFAE00","urgent":false}"##
+    );
+}
+
+#[test]
+fn charging_is_never_urgent() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Charging", 5_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(
+        sysfs.stdout(&["--format", "i3bar", "--color-charging", "none"]),
+        r#"{"name":"battery","full_text":"10.00% (+3:30)","short_text":"10%","urgent":false}"#
+    );
+}
+
+#[test]
+fn waybar_lists_every_battery() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .energy_battery("BAT1", "Discharging", 10_000_000, 50_000_000, 20_000_000, 100);
+    assert_eq!(
+        sysfs.stdout(&["--format", "waybar"]),
+        r#"{"text":"50.00% (-2:30)","tooltip":"BAT0: 80.00%, 0.00 W, threshold 80%\nBAT1: 20.00%, 20.00 W, threshold 100%","class":"discharging","percentage":50,"alt":"discharging","percentage_full":50,"percentage_design":50,"percentage_threshold":56}"#
+    );
+}
+
+#[test]
+fn waybar_critical_class() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 5_000_000, 50_000_000, 10_000_000, 80);
+    assert!(sysfs.stdout(&["--format", "waybar"]).contains(r#""class":"critical","percentage":10,"alt":"discharging""#));
+}
+
+#[test]
+fn polybar_critical_with_color_tags() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 5_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(
+        sysfs.stdout(&["--format", "polybar", "--ramp-discharging", "E,L,M,H,F"]),
+        "%{F#FF0000}E 10.00% (-0:30)%{F-}"
+    );
+}
+
+#[test]
+fn lemonbar_without_color() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Unknown", 35_000_000, 50_000_000, 0, 80);
+    assert_eq!(sysfs.stdout(&["--format", "lemonbar", "--ramp-passive", "E,L,M,H,F"]), "H 70.00%");
+}
+
+#[test]
+fn template_format() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    assert_eq!(sysfs.stdout(&["--format", "BAT {percent:.0}%[ ({sign}{time})]"]), "BAT 50% (-2:30)");
+}
+
+#[test]
+fn invalid_template_is_a_usage_error() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    let output = sysfs.run(&["--format", "{percent} {bogus}"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("unknown placeholder '{bogus}'"));
+}
+
+#[test]
+fn design_capacity_basis() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Charging", 30_000_000, 40_000_000, 10_000_000, 100)
+        .supply("BAT0", &[("energy_full_design", "50000000")]);
+    assert_eq!(sysfs.stdout(&[]), "75.00% (+1:00)");
+    // Time-to-full is capped by the capacity the worn battery still holds
+    assert_eq!(sysfs.stdout(&["--capacity-basis", "design"]), "60.00% (+1:00)");
+    assert_eq!(
+        sysfs.stdout(&["--capacity-basis", "design", "--format", "{percent:.0} {percent_full:.0} {percent_design:.0} {bat0.percent:.0}"]),
+        "60 75 60 60"
+    );
+    assert!(sysfs.stdout(&["--format", "waybar"]).ends_with(r#""percentage":75,"alt":"charging","percentage_full":75,"percentage_design":60,"percentage_threshold":75}"#));
+    assert_eq!(sysfs.run(&["--capacity-basis", "new"]).status.code(), Some(2));
+}
+
+#[test]
+fn waiting_to_charge_status() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Not charging", 35_000_000, 50_000_000, 0, 80)
+        .supply("BAT0", &[("charge_control_start_threshold", "60")]);
+    assert_eq!(sysfs.stdout(&["--format", "{status}[ {sign}{time}]"]), "waiting");
+    assert!(sysfs.stdout(&["--format", "waybar"]).contains(r#""class":"waiting""#));
+}
+
+#[test]
+fn default_threshold_option() {
+    let sysfs = FakeSysfs::new();
+    sysfs.supply("BAT0", &[("type", "Battery"), ("status", "Charging"), ("energy_now", "20000000"), ("energy_full", "50000000"), ("power_now", "10000000")]);
+    assert_eq!(sysfs.stdout(&[]), "40.00% (+2:00)");
+    assert_eq!(sysfs.stdout(&["--default-threshold", "100"]), "40.00% (+3:00)");
+}
+
+#[test]
+fn threshold_capacity_basis() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Charging", 10_000_000, 20_000_000, 3_000_000, 80)
+        .energy_battery("BAT1", "Charging", 40_000_000, 80_000_000, 3_000_000, 100);
+    assert_eq!(sysfs.stdout(&["--capacity-basis", "threshold", "--format", "{percent:.1} {percent_full:.1} {bat0.percent_threshold:.1}"]), "52.1 50.0 62.5");
+}
+
+#[test]
+fn mixed_status_follows_net_flow() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Charging", 20_000_000, 50_000_000, 15_000_000, 100)
+        .energy_battery("BAT1", "Discharging", 30_000_000, 50_000_000, 5_000_000, 100);
+    // Net 10 W into the batteries, 50 Wh short of full
+    assert_eq!(sysfs.stdout(&[]), "50.00% (+5:00) +BAT0 -BAT1");
+    assert!(sysfs.stdout(&["--format", "i3blocks-json"]).starts_with(r#"{"full_text":"50.00% (+5:00) +BAT0 -BAT1","short_text":"50%""#));
+    assert_eq!(sysfs.stdout(&["--format", "lemonbar", "--ramp-charging", "C", "--color-charging", "none"]), "C 50.00% (+5:00) +BAT0 -BAT1");
+    assert_eq!(sysfs.stdout(&["--format", "{status} {active} {flow_w:.0} {bat1.flow_w:.0}[ {sign}{time}]"]), "mixed BAT0,BAT1 10 -5 +5:00");
+    assert!(sysfs.stdout(&["--format", "waybar"]).contains(r#""class":"mixed""#));
+}
+
+#[test]
+fn active_battery_indicator() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .energy_battery("BAT1", "Discharging", 30_000_000, 50_000_000, 5_000_000, 100);
+    assert_eq!(sysfs.stdout(&["--format", "{percent:.0}%[ {active}]"]), "70% BAT1");
+    sysfs.energy_battery("BAT1", "Unknown", 30_000_000, 50_000_000, 0, 100);
+    assert_eq!(sysfs.stdout(&["--format", "{percent:.0}%[ {active}]"]), "70%");
+}
+
+#[test]
+fn per_battery_mode() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 39_000_000, 50_000_000, 0, 80)
+        .energy_battery("BAT1", "Discharging", 6_000_000, 50_000_000, 3_600_000, 100);
+    assert_eq!(sysfs.stdout(&["--per-battery"]), "BAT0 78% | BAT1 12% (-1:40)");
+    assert_eq!(sysfs.stdout(&["--per-battery", "--battery-order", "percentage", "--battery-separator", " / "]), "BAT1 12% (-1:40) / BAT0 78%");
+    assert_eq!(sysfs.stdout(&["--battery-order=BAT1", "--per-battery"]), "BAT1 12% (-1:40) | BAT0 78%");
+    // Aggregated unless per-battery
+    assert_eq!(sysfs.stdout(&["--battery-order", "percentage"]), "45.00% (-12:30)");
+
+    let waybar = sysfs.stdout(&["--per-battery", "--format", "waybar"]);
+    assert!(waybar.starts_with(r#"{"text":"BAT0 78% | BAT1 12% (-1:40)""#), "{}", waybar);
+    assert!(waybar.ends_with(r#""batteries":[{"name":"BAT0","percentage":78,"status":"passive","threshold":80},{"name":"BAT1","percentage":12,"status":"discharging","threshold":100,"time":"1:40"}]}"#), "{}", waybar);
+    assert!(!sysfs.stdout(&["--format", "waybar"]).contains("batteries"));
+    assert!(sysfs.stdout(&["--per-battery", "--format", "i3blocks-json"]).starts_with(r#"{"full_text":"BAT0 78% | BAT1 12% (-1:40)""#));
+}
+
+#[test]
+fn reports_external_power() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80);
+    assert_eq!(sysfs.stdout(&["--format", "{percent:.0}%[ {ac}]"]), "80%");
+    assert!(!sysfs.stdout(&["--format", "waybar"]).contains("ac_online"));
+    sysfs.ac(true);
+    assert_eq!(sysfs.stdout(&["--format", "{percent:.0}%[ {ac}]"]), "80% online");
+    assert!(sysfs.stdout(&["--format", "waybar"]).contains(r#""ac_online":true"#));
+    sysfs.ac(false);
+    assert_eq!(sysfs.stdout(&["--format", "{percent:.0}%[ {ac}]"]), "80% offline");
+}
+
+#[test]
+fn include_and_exclude_options() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .energy_battery("BAT1", "Unknown", 10_000_000, 50_000_000, 0, 80);
+    assert_eq!(sysfs.stdout(&[]), "50.00%");
+    assert_eq!(sysfs.stdout(&["--include", "*1"]), "20.00%");
+    assert_eq!(sysfs.stdout(&["--exclude=BAT1"]), "80.00%");
+    assert_eq!(sysfs.stdout(&["--include", "BAT0", "--include", "BAT1", "--exclude", "*0"]), "20.00%");
+    assert_eq!(sysfs.run(&["--exclude", "BAT*"]).status.code(), Some(6));
+}
+
+#[test]
+fn peripherals_option() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .supply("hidpp_battery_0", &[("type", "Battery"), ("scope", "Device"), ("model_name", "MX Master 3"), ("capacity", "85"), ("status", "Discharging")])
+        .supply("hidpp_battery_1", &[("type", "Battery"), ("scope", "Device"), ("capacity", "5"), ("status", "Discharging")]);
+    assert_eq!(sysfs.stdout(&["--peripherals"]), "hidpp_battery_0  MX Master 3  85%  discharging\nhidpp_battery_1  hidpp_battery_1  5%  discharging  critical");
+    assert!(sysfs.stdout(&["--peripherals", "--json"]).contains(r#""model_name": "MX Master 3""#));
+
+    let ramp = ["--ramp-discharging", "a,b,c,d"];
+    assert_eq!(sysfs.stdout(&ramp), "80.00%");
+    assert_eq!(sysfs.stdout(&[&ramp[..], &["--peripheral-icons"]].concat()), "80.00%  d85% a5%!");
+    assert_eq!(sysfs.stdout(&[&ramp[..], &["--peripheral-icons", "--format", "{percent:.0}%[ {peripherals}]"]].concat()), "80% d85% a5%!");
+    assert_eq!(sysfs.stdout(&[&ramp[..], &["--peripheral-icons", "--format", "polybar", "--ramp-passive", "E,L,M,H,F"]].concat()), "F 80.00%  d85% a5%!");
+}
+
+#[test]
+fn unreadable_adapter_keeps_status() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80).supply("AC", &[("type", "Mains"), ("online", "x")]);
+    let output = sysfs.run(&[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "80.00%");
+}
+
+#[test]
+fn unreadable_peripheral_keeps_status() {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .supply("hidpp_battery_0", &[("type", "Battery"), ("capacity", "85")]);
+    std::fs::create_dir_all(sysfs.root().join("hidpp_battery_0/scope")).unwrap();
+    let output = sysfs.run(&["--peripheral-icons"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "80.00%");
+}
@@ -0,0 +1,141 @@
+//! Helpers for building fake 'power_supply' trees in temporary directories
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary sysfs 'power_supply' directory, removed again when dropped
+pub struct FakeSysfs {
+    root: PathBuf,
+}
+
+impl FakeSysfs {
+    pub fn new() -> FakeSysfs {
+        let root = std::env::temp_dir().join(format!(
+            "poly-battery-status-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&root).unwrap();
+        FakeSysfs { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write given attributes of a power supply, creating its directory as needed
+    pub fn supply(&self, name: &str, attributes: &[(&str, &str)]) -> &FakeSysfs {
+        let dir = self.root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        for (attribute, value) in attributes {
+            fs::write(dir.join(attribute), format!("{}\n", value)).unwrap();
+        }
+        self
+    }
+
+    /// Add an energy-based battery, values in µWh and µW
+    pub fn energy_battery(&self, name: &str, status: &str, now: u32, full: u32, power: u32, threshold: u32) -> &FakeSysfs {
+        self.supply(name, &[
+            ("type", "Battery"),
+            ("status", status),
+            ("energy_now", &now.to_string()),
+            ("energy_full", &full.to_string()),
+            ("power_now", &power.to_string()),
+            ("charge_stop_threshold", &threshold.to_string()),
+        ])
+    }
+
+    /// Add a charge-based battery stopping at 80%, values in µAh, µA and µV
+    pub fn charge_battery(&self, name: &str, status: &str, now: u32, full: u32, current: u32, voltage: u32) -> &FakeSysfs {
+        self.supply(name, &[
+            ("type", "Battery"),
+            ("status", status),
+            ("charge_now", &now.to_string()),
+            ("charge_full", &full.to_string()),
+            ("current_now", &current.to_string()),
+            ("voltage_now", &voltage.to_string()),
+            ("voltage_min_design", &voltage.to_string()),
+            ("charge_stop_threshold", "80"),
+        ])
+    }
+
+    /// Add a mains adapter
+    pub fn ac(&self, online: bool) -> &FakeSysfs {
+        self.supply("AC", &[("type", "Mains"), ("online", if online { "1" } else { "0" })])
+    }
+
+    /// Run the binary against this tree with given arguments
+    pub fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_poly-battery-status"))
+            .arg("--sysfs-root")
+            .arg(&self.root)
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    /// Spawn the binary against this tree, e.g. in watch mode, streaming its output lines
+    pub fn spawn(&self, args: &[&str]) -> Running {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_poly-battery-status"))
+            .arg("--sysfs-root")
+            .arg(&self.root)
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        Running { child, lines }
+    }
+
+    /// Run the binary and return its trimmed standard output
+    pub fn stdout(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        String::from_utf8(output.stdout).unwrap().trim_end().to_string()
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A spawned binary, killed again when dropped
+pub struct Running {
+    pub child: Child,
+    pub lines: Receiver<String>,
+}
+
+impl Running {
+    /// Wait for the next output line, for at most a few seconds
+    pub fn next_line(&self) -> Option<String> {
+        self.lines.recv_timeout(std::time::Duration::from_secs(5)).ok()
+    }
+
+    /// Send a signal to the running binary
+    pub fn signal(&self, signal: &str) {
+        Command::new("kill").arg(format!("-{}", signal)).arg(self.child.id().to_string()).status().unwrap();
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::FakeSysfs;
+use poly_battery_status::{Battery, Configuration, Estimator, History, Reading, Record, Sample, State, Status};
+use std::time::Duration;
+
+fn sample(timestamp: f64, energy: u32, power: u32, status: Status) -> Sample {
+    Sample { timestamp, energy, power, status }
+}
+
+fn discharging(current_charge: u32, power_draw: u32) -> Configuration {
+    Configuration::from_batteries(vec![Battery {
+        name: "BAT0".to_string(),
+        status: Status::Discharging,
+        current_charge,
+        max_charge: 50_000_000,
+        design_charge: None,
+        power_draw,
+        tlp_threshold: 0.8,
+        start_threshold: None,
+    }])
+}
+
+#[test]
+fn instant_uses_latest_draw() {
+    let mut history = History::default();
+    history.push(sample(0f64, 30_000_000, 5_000_000, Status::Discharging), Duration::from_secs(600));
+    history.push(sample(60f64, 29_000_000, 20_000_000, Status::Discharging), Duration::from_secs(600));
+    assert_eq!(Estimator::Instant.power_draw(&history), Some(20_000_000));
+}
+
+#[test]
+fn ema_dampens_spikes() {
+    let mut history = History::default();
+    for i in 0..10 {
+        history.push(sample(i as f64 * 5f64, 30_000_000, 10_000_000, Status::Discharging), Duration::from_secs(900));
+    }
+    history.push(sample(50f64, 30_000_000, 40_000_000, Status::Discharging), Duration::from_secs(900));
+    let draw = Estimator::Ema(Duration::from_secs(300)).power_draw(&history).unwrap();
+    assert!(draw > 10_000_000 && draw < 11_000_000, "draw was {}", draw);
+}
+
+#[test]
+fn regression_uses_slope_of_energy() {
+    let mut history = History::default();
+    // Losing 10 Wh per hour, while the instantaneous draw spikes
+    for i in 0..5 {
+        let t = i as f64 * 60f64;
+        history.push(sample(t, 30_000_000 - (t / 3600f64 * 10_000_000f64) as u32, 25_000_000, Status::Discharging), Duration::from_secs(600));
+    }
+    let draw = Estimator::Regression(Duration::from_secs(600)).power_draw(&history).unwrap();
+    assert!((9_990_000..=10_010_000).contains(&draw), "draw was {}", draw);
+}
+
+#[test]
+fn regression_falls_back_without_slope() {
+    let mut history = History::default();
+    history.push(sample(0f64, 30_000_000, 7_000_000, Status::Discharging), Duration::from_secs(600));
+    history.push(sample(60f64, 30_000_000, 8_000_000, Status::Discharging), Duration::from_secs(600));
+    assert_eq!(Estimator::Regression(Duration::from_secs(600)).power_draw(&history), Some(8_000_000));
+}
+
+#[test]
+fn status_change_resets_smoothing() {
+    let mut history = History::default();
+    history.push(sample(0f64, 30_000_000, 40_000_000, Status::Charging), Duration::from_secs(600));
+    history.push(sample(60f64, 30_000_000, 10_000_000, Status::Discharging), Duration::from_secs(600));
+    assert_eq!(history.current_run().len(), 1);
+    assert_eq!(Estimator::Ema(Duration::from_secs(300)).power_draw(&history), Some(10_000_000));
+}
+
+#[test]
+fn old_samples_are_dropped() {
+    let mut history = History::default();
+    history.push(sample(0f64, 30_000_000, 10_000_000, Status::Discharging), Duration::from_secs(60));
+    history.push(sample(120f64, 30_000_000, 10_000_000, Status::Discharging), Duration::from_secs(60));
+    assert_eq!(history.samples.len(), 1);
+}
+
+#[test]
+fn estimate_replaces_time() {
+    let config = discharging(25_000_000, 20_000_000);
+    let mut history = History::default();
+    history.push(sample(0f64, 25_000_000, 10_000_000, Status::Discharging), Duration::from_secs(900));
+    history.push(sample(1f64, 25_000_000, 10_000_000, Status::Discharging), Duration::from_secs(900));
+    history.push(Sample::new(&config, 2f64), Duration::from_secs(900));
+    let time = Estimator::Ema(Duration::from_secs(300)).estimate(&config, &history);
+    assert!(time > Duration::from_secs(2 * 3600), "time was {:?}", time);
+}
+
+#[test]
+fn state_file_smooths_one_shot_invocations() {
+    let sysfs = FakeSysfs::new();
+    // 25 Wh left with a momentary 20 W draw, after losing 10 Wh per hour over the last minutes
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 20_000_000, 80);
+    let now = poly_battery_status::estimate::unix_time();
+    let state_file = sysfs.root().join("state.json");
+    for ago in &[240f64, 180f64, 120f64, 60f64] {
+        let energy = 25_000_000 + (ago / 3600f64 * 10_000_000f64) as u32;
+        let reading = Reading { name: "BAT0".to_string(), energy, power: 20_000_000, status: Status::Discharging };
+        State::update(&state_file, Record { timestamp: now - ago, batteries: vec![reading] }, Duration::from_secs(600)).unwrap();
+    }
+
+    let state = state_file.to_str().unwrap();
+    let smoothed = sysfs.stdout(&["--estimator", "regression", "--state-file", state]);
+    assert!(smoothed == "50.00% (-2:29)" || smoothed == "50.00% (-2:30)", "output was {}", smoothed);
+    assert_eq!(sysfs.stdout(&["--state-file", state]), "50.00% (-1:15)");
+    assert_eq!(State::load(&state_file).records.len(), 6);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::FakeSysfs;
+use poly_battery_status::graph::{bin, render, Size};
+use poly_battery_status::log::{append, LogBattery};
+use poly_battery_status::{LogEntry, LogFormat, Rotation, Status};
+
+fn entry(timestamp: u64, percentage: f32, power_now: u32, status: Status) -> LogEntry {
+    LogEntry {
+        timestamp,
+        percentage,
+        status,
+        batteries: vec![LogBattery {
+            name: "BAT0".to_string(),
+            energy_now: (percentage * 500_000f32) as u32,
+            energy_full: 50_000_000,
+            power_now,
+            status,
+            threshold: 80f32,
+        }],
+    }
+}
+
+#[test]
+fn bins_average_readings_per_column() {
+    let entries = vec![
+        entry(0, 40f32, 10_000_000, Status::Discharging),
+        entry(10, 60f32, 20_000_000, Status::Charging),
+        entry(250, 30f32, 5_000_000, Status::Discharging),
+    ];
+    let bins = bin(&entries, 0, 400, 4);
+    let first = bins[0].unwrap();
+    assert_eq!(first.percentage, 50f32);
+    assert_eq!(first.power, 15f32);
+    assert_eq!(first.status, Status::Charging);
+    assert_eq!(bins[1], None);
+    assert_eq!(bins[2].unwrap().status, Status::Discharging);
+    assert_eq!(bins[3], None);
+}
+
+#[test]
+fn renders_bars_and_status_strip() {
+    let entries = vec![
+        entry(0, 100f32, 10_000_000, Status::Charging),
+        entry(100, 50f32, 5_000_000, Status::Discharging),
+        entry(300, 0f32, 0, Status::Passive),
+    ];
+    let chart = render(&entries, 0, 400, Size { width: 11, height: 2 });
+    let lines: Vec<&str> = chart.lines().collect();
+    assert_eq!(lines[0], " 100% │█   ");
+    assert_eq!(lines[1], "   0% │██  ");
+    assert_eq!(lines[2], "      │+- ·");
+    assert_eq!(lines[3], "  10W │█▄  ");
+    // The axis is squeezed but stays legible
+    assert_eq!(lines[4], "       -0h now");
+    assert_eq!(lines.len(), 5);
+}
+
+#[test]
+fn bins_into_at_least_one_column() {
+    let columns = bin(&[entry(10, 40f32, 10_000_000, Status::Discharging)], 0, 100, 0);
+    assert_eq!(columns.len(), 1);
+    assert_eq!(columns[0].unwrap().percentage, 40f32);
+}
+
+#[test]
+fn prints_graph_of_log() {
+    let sysfs = FakeSysfs::new();
+    let log = sysfs.root().join("history.jsonl");
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    for (minutes, status) in [(90, Status::Discharging), (30, Status::Charging)] {
+        append(&log, LogFormat::Jsonl, &entry(now - minutes * 60, 50f32, 8_000_000, status), &Rotation::default()).unwrap();
+    }
+    let chart = sysfs.stdout(&["graph", "--hours", "2", "--width", "31", "--height", "4", log.to_str().unwrap()]);
+    let strip = chart.lines().nth(4).unwrap();
+    assert!(strip.contains('-') && strip.contains('+'), "{}", chart);
+    assert!(chart.lines().all(|line| line.chars().count() <= 31), "{}", chart);
+    assert!(chart.ends_with("now"));
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::FakeSysfs;
+use poly_battery_status::health::aggregate_health;
+use poly_battery_status::{get_battery_health, get_battery_healths};
+
+fn thinkpad(sysfs: &FakeSysfs) {
+    sysfs.energy_battery("BAT0", "Discharging", 30_000_000, 45_000_000, 5_000_000, 80);
+    sysfs.supply("BAT0", &[
+        ("energy_full_design", "60000000"),
+        ("cycle_count", "312"),
+        ("manufacturer", "SMP"),
+        ("model_name", "01AV430"),
+        ("technology", "Li-poly"),
+        ("serial_number", "1234"),
+    ]);
+    sysfs.charge_battery("BAT1", "Discharging", 2_000_000, 2_000_000, 0, 10_000_000);
+    sysfs.supply("BAT1", &[("charge_full_design", "2000000")]);
+}
+
+#[test]
+fn reads_design_capacity_and_identification() {
+    let sysfs = FakeSysfs::new();
+    thinkpad(&sysfs);
+    let battery = get_battery_health(sysfs.root(), "BAT0").unwrap();
+    assert_eq!(battery.energy_full_design, Some(60_000_000));
+    assert_eq!(battery.cycle_count, Some(312));
+    assert_eq!(battery.model_name.as_deref(), Some("01AV430"));
+    assert_eq!(battery.health(), Some(75f32));
+    assert_eq!(battery.wear(), Some(25f32));
+
+    // Charge-based design capacity is converted using the design voltage
+    let battery = get_battery_health(sysfs.root(), "BAT1").unwrap();
+    assert_eq!(battery.energy_full_design, Some(20_000_000));
+    assert_eq!(battery.health(), Some(100f32));
+    assert_eq!(battery.manufacturer, None);
+}
+
+#[test]
+fn missing_design_capacity_leaves_health_unknown() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Full", 45_000_000, 45_000_000, 0, 80);
+    let battery = get_battery_health(sysfs.root(), "BAT0").unwrap();
+    assert_eq!(battery.health(), None);
+    assert_eq!(aggregate_health(&[battery]), None);
+}
+
+#[test]
+fn aggregates_over_combined_capacity() {
+    let sysfs = FakeSysfs::new();
+    thinkpad(&sysfs);
+    let batteries = get_battery_healths(sysfs.root()).unwrap();
+    // (45 + 20) / (60 + 20)
+    assert_eq!(aggregate_health(&batteries), Some(81.25));
+}
+
+#[test]
+fn prints_health_table_and_json() {
+    let sysfs = FakeSysfs::new();
+    thinkpad(&sysfs);
+    let table = sysfs.stdout(&["health"]);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].starts_with("BAT0      75.00%   25.00%   45.00 Wh   60.00 Wh     312  SMP  01AV430  Li-poly  1234"), "{}", table);
+    assert!(lines[3].starts_with("total     81.25%   18.75%"), "{}", table);
+
+    let json: serde_json::Value = serde_json::from_str(&sysfs.stdout(&["health", "--json"])).unwrap();
+    assert_eq!(json["health"], 81.25);
+    assert_eq!(json["batteries"][0]["cycle_count"], 312);
+    assert_eq!(json["batteries"][0]["wear"], 25.0);
+    assert_eq!(json["batteries"][1]["serial_number"], serde_json::Value::Null);
+}
+
+#[test]
+fn health_without_batteries_fails() {
+    let sysfs = FakeSysfs::new();
+    sysfs.ac(true);
+    let output = sysfs.run(&["health"]);
+    assert_eq!(output.status.code(), Some(6));
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use common::FakeSysfs;
+use poly_battery_status::log::{append, read_log, read_log_with_rotated, rotated_path};
+use poly_battery_status::{get_configuration, LogEntry, LogFormat, Rotation, Status};
+use std::fs;
+use std::time::Duration;
+
+fn two_batteries() -> FakeSysfs {
+    let sysfs = FakeSysfs::new();
+    sysfs
+        .energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80)
+        .energy_battery("BAT1", "Discharging", 10_000_000, 50_000_000, 20_000_000, 100);
+    sysfs
+}
+
+#[test]
+fn csv_log_has_a_row_per_battery() {
+    let sysfs = two_batteries();
+    let log = sysfs.root().join("history.csv");
+    let output = sysfs.run(&["log", log.to_str().unwrap()]);
+    assert!(output.status.success());
+    let contents = fs::read_to_string(&log).unwrap();
+    let lines: Vec<&str> = contents.lines().collect();
+    assert_eq!(lines[0], "timestamp,percentage,status,battery,energy_now,energy_full,power_now,battery_status,threshold");
+    assert!(lines[1].ends_with(",50.00,discharging,BAT0,40000000,50000000,0,passive,80"));
+    assert!(lines[2].ends_with(",50.00,discharging,BAT1,10000000,50000000,20000000,discharging,100"));
+}
+
+#[test]
+fn jsonl_log_round_trips() {
+    let sysfs = two_batteries();
+    let log = sysfs.root().join("history.jsonl");
+    sysfs.run(&["log", log.to_str().unwrap()]);
+    sysfs.run(&["log", "--log-format", "jsonl", log.to_str().unwrap()]);
+    let entries = read_log(&log, LogFormat::Jsonl).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].status, Status::Discharging);
+    assert_eq!(entries[0].batteries.len(), 2);
+    assert_eq!(entries[0].batteries[1].power_now, 20_000_000);
+    assert_eq!(entries[0].batteries[0].threshold, 80f32);
+}
+
+#[test]
+fn csv_log_round_trips() {
+    let sysfs = two_batteries();
+    let log = sysfs.root().join("history.csv");
+    let config = get_configuration(sysfs.root()).unwrap();
+    for timestamp in &[100, 200] {
+        append(&log, LogFormat::Csv, &LogEntry::new(&config, *timestamp), &Rotation::default()).unwrap();
+    }
+    let entries = read_log(&log, LogFormat::Csv).unwrap();
+    assert_eq!(entries, vec![LogEntry::new(&config, 100), LogEntry::new(&config, 200)]);
+}
+
+#[test]
+fn rotates_by_size_keeping_generations() {
+    let sysfs = two_batteries();
+    let log = sysfs.root().join("history.jsonl");
+    let config = get_configuration(sysfs.root()).unwrap();
+    let rotation = Rotation { max_size: Some(1), max_age: None, keep: 2 };
+    for timestamp in 0..4 {
+        append(&log, LogFormat::Jsonl, &LogEntry::new(&config, timestamp), &rotation).unwrap();
+    }
+    assert!(rotated_path(&log, 2).exists());
+    assert!(!rotated_path(&log, 3).exists());
+    let timestamps: Vec<u64> = read_log_with_rotated(&log, LogFormat::Jsonl).unwrap().iter().map(|e| e.timestamp).collect();
+    assert_eq!(timestamps, [1, 2, 3]);
+}
+
+#[test]
+fn rotates_by_age() {
+    let sysfs = two_batteries();
+    let log = sysfs.root().join("history.jsonl");
+    let config = get_configuration(sysfs.root()).unwrap();
+    let rotation = Rotation { max_size: None, max_age: Some(Duration::from_secs(3600)), keep: 1 };
+    for timestamp in &[0, 1800, 3600, 4000] {
+        append(&log, LogFormat::Jsonl, &LogEntry::new(&config, *timestamp), &rotation).unwrap();
+    }
+    assert_eq!(read_log(&rotated_path(&log, 1), LogFormat::Jsonl).unwrap().len(), 2);
+    assert_eq!(read_log(&log, LogFormat::Jsonl).unwrap().len(), 2);
+}
+
+#[test]
+fn log_fails_without_batteries() {
+    let sysfs = FakeSysfs::new();
+    let log = sysfs.root().join("history.jsonl");
+    assert_eq!(sysfs.run(&["log", log.to_str().unwrap()]).status.code(), Some(6));
+    assert!(!log.exists());
+}
+
+#[test]
+fn log_defaults_to_state_directory() {
+    let sysfs = two_batteries();
+    let state_home = sysfs.root().join("xdg-state");
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_poly-battery-status"))
+        .args(["--sysfs-root", sysfs.root().to_str().unwrap(), "log"])
+        .env("XDG_STATE_HOME", &state_home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(state_home.join("poly-battery-status/history.jsonl").exists());
+}
+
+#[test]
+fn sigusr1_forces_logging() {
+    let sysfs = two_batteries();
+    let log = sysfs.root().join("history.jsonl");
+    let mut running = sysfs.spawn(&["log", "--watch", "1m", "--no-uevents", log.to_str().unwrap()]);
+    let readings = |count: usize| {
+        (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(50));
+            read_log(&log, LogFormat::Jsonl).is_ok_and(|entries| entries.len() == count)
+        })
+    };
+    assert!(readings(1));
+    running.signal("USR1");
+    assert!(readings(2));
+    assert!(running.child.try_wait().unwrap().is_none());
+}
+
+#[test]
+fn oversized_rotation_size_is_rejected() {
+    let sysfs = two_batteries();
+    let log = sysfs.root().join("history.jsonl");
+    let output = sysfs.run(&["log", "--log-max-size", "99999999999999G", log.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Invalid size: 99999999999999G"));
+}
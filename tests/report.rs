@@ -0,0 +1,137 @@
+mod common;
+
+use common::FakeSysfs;
+use poly_battery_status::log::{append, LogBattery};
+use poly_battery_status::report::{format_date, format_table, summarize};
+use poly_battery_status::{LogEntry, LogFormat, Rotation, Status};
+
+/// 2024-01-01T00:00:00Z
+const NEW_YEAR: u64 = 1_704_067_200;
+
+fn entry(hours: u64, minutes: u64, percentage: f32, status: Status) -> LogEntry {
+    LogEntry {
+        timestamp: NEW_YEAR + hours * 3600 + minutes * 60,
+        percentage,
+        status,
+        batteries: vec![LogBattery {
+            name: "BAT0".to_string(),
+            energy_now: (percentage * 500_000f32) as u32,
+            energy_full: 50_000_000,
+            power_now: 0,
+            status,
+            threshold: 80f32,
+        }],
+    }
+}
+
+fn day() -> Vec<LogEntry> {
+    vec![
+        entry(8, 0, 70f32, Status::Charging),
+        entry(8, 10, 75f32, Status::Charging),
+        entry(8, 20, 80f32, Status::Passive),
+        entry(8, 30, 80f32, Status::Passive),
+        entry(8, 40, 78f32, Status::Discharging),
+        entry(8, 50, 70f32, Status::Discharging),
+        entry(9, 0, 62f32, Status::Discharging),
+        // Suspended for an hour
+        entry(10, 0, 60f32, Status::Discharging),
+        entry(10, 10, 61f32, Status::Charging),
+        entry(24, 10, 90f32, Status::Discharging),
+    ]
+}
+
+#[test]
+fn dates_from_days_since_epoch() {
+    assert_eq!(format_date(0), "1970-01-01");
+    assert_eq!(format_date(11_016), "2000-02-29");
+    assert_eq!(format_date(19_723), "2024-01-01");
+}
+
+#[test]
+fn summarizes_per_day() {
+    let summaries = summarize(&day());
+    assert_eq!(summaries.len(), 2);
+
+    let first = &summaries[0];
+    assert_eq!(first.date, "2024-01-01");
+    assert_eq!(first.readings, 9);
+    // 8 Wh lost over 20 minutes
+    assert!((first.average_discharge_w.unwrap() - 24f32).abs() < 0.01);
+    assert_eq!(first.longest_on_battery, 20 * 60);
+    assert_eq!(first.charge_cycles, 1);
+    assert_eq!(first.deepest_discharge, 60f32);
+    assert_eq!(first.above_threshold, 20 * 60);
+
+    let second = &summaries[1];
+    assert_eq!(second.date, "2024-01-02");
+    assert_eq!(second.readings, 1);
+    assert_eq!(second.average_discharge_w, None);
+}
+
+#[test]
+fn table_lists_every_day() {
+    let table = format_table(&summarize(&day()));
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with("2024-01-01"));
+    assert!(lines[1].contains("24.00 W"));
+    assert!(lines[1].contains("60.00%"));
+    assert!(lines[2].starts_with("2024-01-02"));
+}
+
+#[test]
+fn report_subcommand_prints_json() {
+    let sysfs = FakeSysfs::new();
+    let log = sysfs.root().join("history.jsonl");
+    for entry in day() {
+        append(&log, LogFormat::Jsonl, &entry, &Rotation::default()).unwrap();
+    }
+    let output = sysfs.stdout(&["report", "--json", log.to_str().unwrap()]);
+    let summaries: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(summaries[0]["date"], "2024-01-01");
+    assert_eq!(summaries[0]["charge_cycles"], 1);
+    assert_eq!(summaries[1]["readings"], 1);
+}
+
+#[test]
+fn report_of_missing_log_fails() {
+    let sysfs = FakeSysfs::new();
+    let log = sysfs.root().join("missing.jsonl");
+    assert_eq!(sysfs.run(&["report", log.to_str().unwrap()]).status.code(), Some(1));
+}
+
+#[test]
+fn unordered_entries_are_sorted() {
+    let mut entries = day();
+    entries.reverse();
+    assert_eq!(summarize(&entries), summarize(&day()));
+
+    let sysfs = FakeSysfs::new();
+    let log = sysfs.root().join("history.jsonl");
+    for entry in [entry(8, 10, 70f32, Status::Discharging), entry(8, 0, 75f32, Status::Discharging)] {
+        append(&log, LogFormat::Jsonl, &entry, &Rotation::default()).unwrap();
+    }
+    let output = sysfs.stdout(&["report", "--json", log.to_str().unwrap()]);
+    let summaries: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(summaries.as_array().unwrap().len(), 1);
+    assert_eq!(summaries[0]["longest_on_battery"], 10 * 60);
+}
+
+#[test]
+fn readings_across_midnight_count_towards_both_days() {
+    let summaries = summarize(&[entry(23, 50, 60f32, Status::Discharging), entry(24, 10, 56f32, Status::Discharging)]);
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(summaries[0].longest_on_battery, 10 * 60);
+    assert_eq!(summaries[1].longest_on_battery, 10 * 60);
+    // 1 Wh lost over each 10 minutes
+    assert!((summaries[0].average_discharge_w.unwrap() - 6f32).abs() < 0.01);
+    assert!((summaries[1].average_discharge_w.unwrap() - 6f32).abs() < 0.01);
+    assert_eq!(summaries[0].readings, 1);
+    assert_eq!(summaries[1].deepest_discharge, 56f32);
+}
+
+#[test]
+fn threshold_allows_settling_below_it() {
+    let summaries = summarize(&[entry(8, 0, 79.8, Status::Passive), entry(8, 10, 79.8, Status::Passive)]);
+    assert_eq!(summaries[0].above_threshold, 10 * 60);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::FakeSysfs;
+use poly_battery_status::{Reading, Record, State, Status};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn record(timestamp: f64, bat0: (u32, Status), bat1: (u32, Status)) -> Record {
+    Record {
+        timestamp,
+        batteries: vec![
+            Reading { name: "BAT0".to_string(), energy: bat0.0, power: 1_000_000, status: bat0.1 },
+            Reading { name: "BAT1".to_string(), energy: bat1.0, power: 2_000_000, status: bat1.1 },
+        ],
+    }
+}
+
+#[test]
+fn records_aggregate_into_samples() {
+    let sample = record(10f64, (30_000_000, Status::Passive), (5_000_000, Status::Discharging)).sample();
+    assert_eq!(sample.energy, 35_000_000);
+    assert_eq!(sample.power, 3_000_000);
+    assert_eq!(sample.status, Status::Discharging);
+}
+
+#[test]
+fn update_prunes_by_age() {
+    let sysfs = FakeSysfs::new();
+    let path = sysfs.root().join("state/state.json");
+    for timestamp in &[0f64, 30f64, 90f64] {
+        State::update(&path, record(*timestamp, (1, Status::Passive), (1, Status::Passive)), Duration::from_secs(60)).unwrap();
+    }
+    let timestamps: Vec<f64> = State::load(&path).records.iter().map(|r| r.timestamp).collect();
+    assert_eq!(timestamps, [30f64, 90f64]);
+}
+
+#[test]
+fn concurrent_updates_are_not_lost() {
+    let sysfs = FakeSysfs::new();
+    let path = Arc::new(sysfs.root().join("state.json"));
+    let writers: Vec<_> = (0..16)
+        .map(|i| {
+            let path = Arc::clone(&path);
+            thread::spawn(move || {
+                let record = record(1000f64 + i as f64, (i, Status::Charging), (i, Status::Passive));
+                State::update(&path, record, Duration::from_secs(3600)).unwrap();
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert_eq!(State::load(&path).records.len(), 16);
+}
+
+#[test]
+fn unreadable_state_starts_over() {
+    let sysfs = FakeSysfs::new();
+    let path = sysfs.root().join("state.json");
+    std::fs::write(&path, "{ truncated").unwrap();
+    let state = State::update(&path, record(0f64, (1, Status::Passive), (1, Status::Passive)), Duration::from_secs(60)).unwrap();
+    assert_eq!(state.records.len(), 1);
+}
+
+#[test]
+fn state_flag_uses_xdg_state_home() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    let state_home = sysfs.root().join("xdg-state");
+    let output = Command::new(env!("CARGO_BIN_EXE_poly-battery-status"))
+        .args(["--sysfs-root", sysfs.root().to_str().unwrap(), "--state"])
+        .env("XDG_STATE_HOME", &state_home)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let state = State::load(&state_home.join("poly-battery-status/state.json"));
+    assert_eq!(state.records.len(), 1);
+    assert_eq!(state.records[0].batteries[0].name, "BAT0");
+    assert_eq!(state.records[0].batteries[0].energy, 25_000_000);
+}
@@ -0,0 +1,64 @@
+use poly_battery_status::{Battery, Configuration, Status, Style, Template};
+
+fn battery(name: &str, status: Status, current_charge: u32, power_draw: u32) -> Battery {
+    Battery { name: name.to_string(), status, current_charge, max_charge: 50_000_000, design_charge: None, power_draw, tlp_threshold: 0.8, start_threshold: None }
+}
+
+fn render(template: &str, batteries: Vec<Battery>) -> String {
+    let template: Template = template.parse().unwrap();
+    template.render(&Configuration::from_batteries(batteries), &Style::default())
+}
+
+#[test]
+fn placeholders_with_precision() {
+    let bats = vec![battery("BAT0", Status::Discharging, 25_000_000, 10_000_000)];
+    assert_eq!(render("{percent}% {percent:.0}% {power_w:.1} W {energy_wh} Wh", bats), "50.00% 50% 10.0 W 25.00 Wh");
+}
+
+#[test]
+fn section_with_time_while_discharging() {
+    let bats = vec![battery("BAT0", Status::Discharging, 25_000_000, 10_000_000)];
+    assert_eq!(render("{percent:.0}%[ ({sign}{time})] {status}", bats), "50% (-2:30) discharging");
+}
+
+#[test]
+fn section_disappears_when_passive() {
+    let bats = vec![battery("BAT0", Status::Passive, 40_000_000, 0)];
+    assert_eq!(render("{percent:.0}%[ ({sign}{time})]", bats), "80%");
+}
+
+#[test]
+fn per_battery_placeholders() {
+    let bats = vec![battery("BAT0", Status::Passive, 40_000_000, 0), battery("BAT1", Status::Discharging, 10_000_000, 5_000_000)];
+    assert_eq!(render("{bat0.percent:.0} {bat1.status} {bat1.threshold:.0}[ {bat2.percent}]", bats), "80 discharging 80");
+}
+
+#[test]
+fn escaped_braces_and_brackets() {
+    let bats = vec![battery("BAT0", Status::Passive, 40_000_000, 0)];
+    assert_eq!(render("{{[[{percent:.0}]]}}", bats), "{[80]}");
+}
+
+#[test]
+fn unknown_placeholder_is_an_error() {
+    let error = "{percent} {voltage}".parse::<Template>().unwrap_err();
+    assert_eq!(error.position, 10);
+    assert!(error.to_string().contains("unknown placeholder '{voltage}'"));
+}
+
+#[test]
+fn battery_only_fields_are_rejected_for_the_aggregate() {
+    assert!("{threshold}".parse::<Template>().is_err());
+    assert!("{bat0.time}".parse::<Template>().is_err());
+}
+
+#[test]
+fn syntax_errors() {
+    assert!("{percent".parse::<Template>().is_err());
+    assert!("percent}".parse::<Template>().is_err());
+    assert!("[{time}".parse::<Template>().is_err());
+    assert!("[[{time}]".parse::<Template>().is_err());
+    assert!("[a[b]]".parse::<Template>().is_err());
+    assert!("{status:.2}".parse::<Template>().is_err());
+    assert!("{percent:2}".parse::<Template>().is_err());
+}
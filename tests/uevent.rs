@@ -0,0 +1,76 @@
+use poly_battery_status::uevent::{Uevent, UeventSocket};
+use poly_battery_status::watch::{wait_for_events, Wakeup};
+use std::os::unix::net::UnixDatagram;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const AC_UNPLUG: &[u8] = b"change@/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+ACTION=change\0DEVPATH=/devices/LNXSYSTM:00/LNXSYBUS:00/ACPI0003:00/power_supply/AC\0\
+SUBSYSTEM=power_supply\0POWER_SUPPLY_NAME=AC\0POWER_SUPPLY_TYPE=Mains\0POWER_SUPPLY_ONLINE=0\0SEQNUM=4242\0";
+
+const USB_ADD: &[u8] = b"add@/devices/pci0000:00/0000:00:14.0/usb1/1-2\0ACTION=add\0SUBSYSTEM=usb\0SEQNUM=4243\0";
+
+/// A socket pair, where uevents written to the first end are received by the second
+fn pair() -> (UnixDatagram, UeventSocket) {
+    let (sender, receiver) = UnixDatagram::pair().unwrap();
+    (sender, UeventSocket::from(receiver))
+}
+
+#[test]
+fn parse_power_supply_uevent() {
+    let event = Uevent::parse(AC_UNPLUG).unwrap();
+    assert_eq!(event.action, "change");
+    assert!(event.devpath.ends_with("/power_supply/AC"));
+    assert_eq!(event.property("POWER_SUPPLY_ONLINE"), Some("0"));
+    assert!(event.is_power_supply());
+}
+
+#[test]
+fn parse_other_subsystem() {
+    let event = Uevent::parse(USB_ADD).unwrap();
+    assert_eq!(event.action, "add");
+    assert!(!event.is_power_supply());
+}
+
+#[test]
+fn udev_messages_are_not_kernel_uevents() {
+    assert_eq!(Uevent::parse(b"libudev\0\xfe\xed\xca\xfe"), None);
+}
+
+#[test]
+fn receive_drains_pending_events() {
+    let (sender, socket) = pair();
+    sender.send(USB_ADD).unwrap();
+    sender.send(AC_UNPLUG).unwrap();
+    let events = socket.receive().unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(socket.receive().unwrap().is_empty());
+}
+
+#[test]
+fn power_supply_event_wakes_early() {
+    let (sender, socket) = pair();
+    let feeder = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        sender.send(AC_UNPLUG).unwrap();
+        sender
+    });
+    let start = Instant::now();
+    assert_eq!(wait_for_events(Some(&socket), Duration::from_secs(10)), Wakeup::PowerSupply);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    feeder.join().unwrap();
+}
+
+#[test]
+fn other_events_do_not_wake() {
+    let (sender, socket) = pair();
+    sender.send(USB_ADD).unwrap();
+    let start = Instant::now();
+    assert_eq!(wait_for_events(Some(&socket), Duration::from_millis(100)), Wakeup::Interval);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+fn no_socket_polls() {
+    assert_eq!(wait_for_events(None, Duration::from_millis(20)), Wakeup::Interval);
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::FakeSysfs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn prints_only_changed_lines() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    let running = sysfs.spawn(&["--watch", "50ms"]);
+    assert_eq!(running.next_line().unwrap(), "50.00% (-2:30)");
+
+    // Several refreshes pass without a change before the charge drops
+    thread::sleep(Duration::from_millis(200));
+    sysfs.supply("BAT0", &[("energy_now", "20000000")]);
+    assert_eq!(running.next_line().unwrap(), "40.00% (-2:00)");
+}
+
+#[test]
+fn sigusr1_forces_refresh() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Discharging", 25_000_000, 50_000_000, 10_000_000, 80);
+    let running = sysfs.spawn(&["--watch", "1m"]);
+    assert_eq!(running.next_line().unwrap(), "50.00% (-2:30)");
+
+    sysfs.supply("BAT0", &[("status", "Unknown")]);
+    running.signal("USR1");
+    assert_eq!(running.next_line().unwrap(), "50.00%");
+}
+
+#[test]
+fn i3bar_protocol_stream() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Unknown", 40_000_000, 50_000_000, 0, 80);
+    let running = sysfs.spawn(&["--watch", "1m", "--format", "i3bar"]);
+    assert_eq!(running.next_line().unwrap(), r#"{"version":1}"#);
+    assert_eq!(running.next_line().unwrap(), "[");
+    assert_eq!(
+        running.next_line().unwrap(),
+        r#"[{"name":"battery","full_text":"80.00%","short_text":"80%","urgent":false}],"#
+    );
+}
+
+#[test]
+fn errors_keep_watching() {
+    let sysfs = FakeSysfs::new();
+    sysfs.energy_battery("BAT0", "Exploding", 25_000_000, 50_000_000, 10_000_000, 80);
+    let running = sysfs.spawn(&["--watch", "50ms"]);
+    assert_eq!(running.next_line().unwrap(), "BAT ERR");
+    sysfs.supply("BAT0", &[("status", "Discharging")]);
+    assert_eq!(running.next_line().unwrap(), "50.00% (-2:30)");
+}